@@ -0,0 +1,232 @@
+use chrono::NaiveDate;
+use csv::Writer;
+use geo::{BooleanOps, HaversineDestination, LineString, MultiPolygon, Point, Polygon};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::calendar::format_date;
+use crate::cli::Args;
+use crate::diagnostics::Diagnostics;
+use crate::generate::{self, Feed};
+use crate::planner::{self, Planner};
+use crate::time::format_time;
+use crate::transfers::DEFAULT_WALKING_SPEED;
+
+// Vertices of each walking buffer's circle
+const CIRCLE_POINTS: usize = 24;
+
+/// Departures sampled for the matrix: every `step` seconds from `start` until `end`.
+pub struct Window {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+/// Typical travel times between the stops our trips serve, in the order they are
+/// first served.
+pub struct Matrix {
+    pub stops: Vec<(String, String, Point)>, // stop_id, stop_name, location
+    pub seconds: Vec<Vec<Option<i64>>>,      // [from][to]; None when not reachable in most departures
+}
+
+/// The median travel time from each stop to every other, leaving at each departure
+/// in the window. A travel time runs from the departure, so waiting for the bus
+/// counts. Departures that can't reach a stop count as slower than any that do.
+pub fn matrix(feed: &Feed, planner: &Planner, window: &Window) -> Result<Matrix, String> {
+    let locations: HashMap<&str, (&str, Point)> = feed
+        .stops
+        .iter()
+        .map(|s| (s.stop_id.as_str(), (s.stop_name.as_str(), Point::new(s.stop_lon, s.stop_lat))))
+        .collect();
+    let mut seen = HashSet::new();
+    let stops: Vec<(String, String, Point)> = feed
+        .stop_times
+        .iter()
+        .filter(|st| seen.insert(st.stop_id.as_str()))
+        .filter_map(|st| {
+            let (name, point) = locations.get(st.stop_id.as_str())?;
+            Some((st.stop_id.clone(), name.to_string(), *point))
+        })
+        .collect();
+
+    let departures: Vec<i64> = (window.start..window.end).step_by(window.step.max(1) as usize).collect();
+    let mut seconds = Vec::new();
+    for (from, ..) in &stops {
+        let mut samples: Vec<Vec<Option<i64>>> = vec![Vec::new(); stops.len()];
+        for &departure in &departures {
+            let arrivals = planner.arrivals(from, departure)?;
+            for (j, (to, ..)) in stops.iter().enumerate() {
+                samples[j].push(arrivals.get(to.as_str()).map(|arrival| arrival - departure));
+            }
+        }
+        let row = stops
+            .iter()
+            .zip(samples)
+            .map(|((to, ..), mut times)| {
+                if to == from {
+                    return Some(0);
+                }
+                times.sort_by_key(|t| t.unwrap_or(i64::MAX));
+                times.get(times.len().saturating_sub(1) / 2).copied().flatten()
+            })
+            .collect();
+        seconds.push(row);
+    }
+    Ok(Matrix { stops, seconds })
+}
+
+/// Writes the matrix with a row per origin and a column per destination stop_id, in
+/// whole minutes.
+fn write_matrix(matrix: &Matrix, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut w = Writer::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let header = ["from_stop_id", "from_stop_name"].into_iter().chain(matrix.stops.iter().map(|(id, ..)| id.as_str()));
+    w.write_record(header)?;
+    for ((stop_id, stop_name, _), row) in matrix.stops.iter().zip(&matrix.seconds) {
+        let minutes = row.iter().map(|t| t.map_or(String::new(), |t| ((t + 30) / 60).to_string()));
+        w.write_record([stop_id.clone(), stop_name.clone()].into_iter().chain(minutes))?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+fn circle(center: Point, radius: f64) -> Polygon {
+    let points: Vec<Point> = (0..CIRCLE_POINTS)
+        .map(|i| center.haversine_destination(i as f64 * 360.0 / CIRCLE_POINTS as f64, radius))
+        .collect();
+    Polygon::new(LineString::from(points), Vec::new())
+}
+
+fn coordinates(polygons: &MultiPolygon) -> Value {
+    let ring = |ring: &LineString| {
+        let round = |v: f64| (v * 1e6).round() / 1e6;
+        Value::Array(ring.points().map(|p| json!([round(p.x()), round(p.y())])).collect())
+    };
+    Value::Array(
+        polygons
+            .iter()
+            .map(|p| Value::Array(std::iter::once(p.exterior()).chain(p.interiors()).map(ring).collect()))
+            .collect(),
+    )
+}
+
+/// Isochrones of each stop as a GeoJSON FeatureCollection: for each of `minutes`, the
+/// area within a walk of the stops it reaches in that time. Each stop reached adds a
+/// circle of the distance walked in the time left, up to `walk` meters.
+pub fn isochrones(matrix: &Matrix, minutes: &[i64], walk: f64) -> Value {
+    let mut features = Vec::new();
+    for ((stop_id, stop_name, _), row) in matrix.stops.iter().zip(&matrix.seconds) {
+        for &limit in minutes {
+            let mut areas: Vec<MultiPolygon> = matrix
+                .stops
+                .iter()
+                .zip(row)
+                .filter_map(|((.., point), time)| {
+                    let time = time.filter(|&t| t <= limit * 60)?;
+                    let radius = walk.min((limit * 60 - time) as f64 * DEFAULT_WALKING_SPEED);
+                    (radius > 0.0).then(|| MultiPolygon::new(vec![circle(*point, radius)]))
+                })
+                .collect();
+            // Unions in pairs keep the shapes being merged small
+            while areas.len() > 1 {
+                areas = areas.chunks(2).map(|pair| pair.iter().skip(1).fold(pair[0].clone(), |a, b| a.union(b))).collect();
+            }
+            let area = areas.pop().unwrap_or_else(|| MultiPolygon::new(Vec::new()));
+            features.push(json!({
+                "type": "Feature",
+                "properties": { "stop_id": stop_id, "stop_name": stop_name, "minutes": limit },
+                "geometry": { "type": "MultiPolygon", "coordinates": coordinates(&area) },
+            }));
+        }
+    }
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// Writes `matrix.csv` and `isochrones.geojson` to `dir`.
+pub fn write(matrix: &Matrix, minutes: &[i64], walk: f64, dir: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    write_matrix(matrix, &dir.join("matrix.csv"))?;
+    fs::write(dir.join("isochrones.geojson"), serde_json::to_string(&isochrones(matrix, minutes, walk))? + "\n")?;
+    Ok(())
+}
+
+/// Regenerates the feed in memory for the date and writes stop-to-stop travel times
+/// and isochrones.
+pub struct Command {
+    pub date: NaiveDate,
+    pub window: Window,
+    pub minutes: Vec<i64>, // Isochrone limits
+    pub walk: f64,         // Meters
+    pub output_dir: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command {
+            date: chrono::Local::now().date_naive(),
+            window: Window { start: 7 * 3600, end: 9 * 3600, step: 600 },
+            minutes: vec![15, 30, 45],
+            walk: 400.0,
+            output_dir: "access".to_string(),
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--date" => command.date = args.date("--date")?,
+                "--start" => command.window.start = args.time("--start")?,
+                "--end" => command.window.end = args.time("--end")?,
+                "--step" => {
+                    let step = args.minutes("--step")?;
+                    if step <= 0 {
+                        return Err(args.error(&format!("Invalid number of minutes: {}", step)));
+                    }
+                    command.window.step = step * 60;
+                }
+                "--minutes" => {
+                    command.minutes = args
+                        .value("--minutes")?
+                        .split(',')
+                        .map(|m| m.trim().parse().map_err(|_| args.error(&format!("Invalid number of minutes: {}", m))))
+                        .collect::<Result<_, _>>()?;
+                }
+                "--walk" => command.walk = args.parse("--walk", "distance")?,
+                "--output" => command.output_dir = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        if command.window.end <= command.window.start {
+            return Err(args.error("--end must be after --start"));
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let feed = generate::build(&generate::Options::in_memory(self.date), diagnostics)?;
+        let planner = planner::build(&feed, &[], self.date, diagnostics);
+        let matrix = matrix(&feed, &planner, &self.window)?;
+        write(&matrix, &self.minutes, self.walk, &self.output_dir)?;
+
+        let pairs: Vec<i64> = matrix
+            .seconds
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().filter(move |&(j, _)| j != i).map(|(_, t)| t.unwrap_or(-1)))
+            .collect();
+        let reached: Vec<i64> = pairs.iter().copied().filter(|&t| t >= 0).collect();
+        println!(
+            "{} stops on {}, leaving {}-{}: {} of {} stop pairs reachable, average {:.1} min, longest {} min",
+            matrix.stops.len(),
+            format_date(self.date),
+            format_time(self.window.start),
+            format_time(self.window.end),
+            reached.len(),
+            pairs.len(),
+            reached.iter().sum::<i64>() as f64 / reached.len().max(1) as f64 / 60.0,
+            reached.iter().max().map_or(0, |t| (t + 30) / 60)
+        );
+        println!("Wrote {}/", self.output_dir);
+        Ok(())
+    }
+}
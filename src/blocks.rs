@@ -0,0 +1,208 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate::{self, Feed};
+use crate::output::write_rows;
+use crate::time::{format_time, parse_time};
+
+/// A trip in one of the built blocks.
+#[derive(Debug, Serialize)]
+pub struct BlockRow {
+    pub service_id: String,
+    pub block_id: String,
+    pub trip_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub start_stop_id: String,
+    pub end_stop_id: String,
+    pub layover_minutes: Option<i64>, // Since the block's previous trip
+    pub manual_block_id: String,      // The block_id given in src/data/
+}
+
+/// Built against manual blocks for one service.
+pub struct Comparison {
+    pub service_id: String,
+    pub trips: usize,
+    pub manual_blocks: usize,
+    pub manual_conflicts: usize, // Trips that cannot follow the previous trip of their manual block
+    pub blocks: usize,
+}
+
+struct BlockTrip<'a> {
+    trip_id: &'a str,
+    block_id: &'a str,
+    start: i64,
+    end: i64,
+    from: &'a str, // First and last stop_id
+    to: &'a str,
+}
+
+/// Whether the same vehicle can run `next` after `prev`.
+fn can_follow(prev: &BlockTrip, next: &BlockTrip, min_layover: i64) -> bool {
+    prev.to == next.from && next.start - prev.end >= min_layover
+}
+
+/// Finds a trip to run after `trip`, moving other trips' predecessors along if that
+/// frees one (an augmenting path of Kuhn's matching algorithm).
+fn assign(trip: usize, followers: &[Vec<usize>], predecessor: &mut [Option<usize>], seen: &mut [bool]) -> bool {
+    for &next in &followers[trip] {
+        if seen[next] {
+            continue;
+        }
+        seen[next] = true;
+        if predecessor[next].is_none_or(|other| assign(other, followers, predecessor, seen)) {
+            predecessor[next] = Some(trip);
+            return true;
+        }
+    }
+    false
+}
+
+/// Chains trips (sorted by start) into the fewest blocks. Every trip a block doesn't
+/// start with follows another, so the fewest blocks come from pairing as many trips
+/// as possible with a predecessor. Trips of the same manual block, then the earliest,
+/// are tried first, so blocks stay close to the manual ones where that costs nothing.
+fn chain(trips: &[BlockTrip], min_layover: i64) -> Vec<Vec<usize>> {
+    let followers: Vec<Vec<usize>> = trips
+        .iter()
+        .map(|prev| {
+            let mut next: Vec<usize> = (0..trips.len()).filter(|&j| can_follow(prev, &trips[j], min_layover)).collect();
+            next.sort_by_key(|&j| (trips[j].block_id != prev.block_id, j));
+            next
+        })
+        .collect();
+    let mut predecessor: Vec<Option<usize>> = vec![None; trips.len()];
+    for trip in 0..trips.len() {
+        assign(trip, &followers, &mut predecessor, &mut vec![false; trips.len()]);
+    }
+
+    let mut successor: Vec<Option<usize>> = vec![None; trips.len()];
+    for (next, prev) in predecessor.iter().enumerate() {
+        if let Some(prev) = prev {
+            successor[*prev] = Some(next);
+        }
+    }
+    (0..trips.len())
+        .filter(|&i| predecessor[i].is_none())
+        .map(|first| std::iter::successors(Some(first), |&i| successor[i]).collect())
+        .collect()
+}
+
+/// Builds blocks from each service's trips, ignoring their block_ids, and checks the
+/// manual blocks against the same rules: a trip must start where the previous trip of
+/// its block ended, at least `min_layover` seconds later.
+pub fn build(feed: &Feed, min_layover: i64, diagnostics: &mut Diagnostics) -> (Vec<BlockRow>, Vec<Comparison>) {
+    let by_trip = feed.stop_times_by_trip();
+
+    let mut services: BTreeMap<&str, Vec<BlockTrip>> = BTreeMap::new();
+    for trip in &feed.trips {
+        let Some(times) = by_trip.get(trip.trip_id.as_str()) else { continue };
+        let (first, last) = (times[0], times[times.len() - 1]);
+        services.entry(&trip.service_id).or_default().push(BlockTrip {
+            trip_id: &trip.trip_id,
+            block_id: &trip.block_id,
+            start: parse_time(&first.departure_time).unwrap_or(0),
+            end: parse_time(&last.arrival_time).unwrap_or(0),
+            from: &first.stop_id,
+            to: &last.stop_id,
+        });
+    }
+
+    let mut rows = Vec::new();
+    let mut comparisons = Vec::new();
+    for (service_id, mut trips) in services {
+        trips.sort_by_key(|t| (t.start, t.trip_id));
+
+        // Manual blocks
+        let mut manual: BTreeMap<&str, Vec<&BlockTrip>> = BTreeMap::new();
+        for trip in trips.iter().filter(|t| !t.block_id.is_empty()) {
+            manual.entry(trip.block_id).or_default().push(trip);
+        }
+        let mut manual_conflicts = 0;
+        for (block_id, block) in &manual {
+            for pair in block.windows(2) {
+                let (prev, next) = (pair[0], pair[1]);
+                let problem = if next.start < prev.end {
+                    format!("starts at {}, before trip {} ends at {}", format_time(next.start), prev.trip_id, format_time(prev.end))
+                } else if prev.to != next.from {
+                    format!("starts at stop {}, but trip {} ends at stop {}", next.from, prev.trip_id, prev.to)
+                } else if !can_follow(prev, next, min_layover) {
+                    format!("leaves {} min after trip {}, less than the minimum layover", (next.start - prev.end) / 60, prev.trip_id)
+                } else {
+                    continue;
+                };
+                manual_conflicts += 1;
+                diagnostics.push(
+                    Diagnostic::warning("block_conflict", format!("Trip in block {} {}", block_id, problem))
+                        .at(Location::trip(next.trip_id)),
+                );
+            }
+        }
+
+        let blocks = chain(&trips, min_layover);
+        for (n, block) in blocks.iter().enumerate() {
+            let block_id = format!("{}_{}", service_id, n + 1);
+            for (k, &i) in block.iter().enumerate() {
+                let trip = &trips[i];
+                rows.push(BlockRow {
+                    service_id: service_id.to_string(),
+                    block_id: block_id.clone(),
+                    trip_id: trip.trip_id.to_string(),
+                    start_time: format_time(trip.start),
+                    end_time: format_time(trip.end),
+                    start_stop_id: trip.from.to_string(),
+                    end_stop_id: trip.to.to_string(),
+                    layover_minutes: (k > 0).then(|| (trip.start - trips[block[k - 1]].end) / 60),
+                    manual_block_id: trip.block_id.to_string(),
+                });
+            }
+        }
+        comparisons.push(Comparison {
+            service_id: service_id.to_string(),
+            trips: trips.len(),
+            manual_blocks: manual.len(),
+            manual_conflicts,
+            blocks: blocks.len(),
+        });
+    }
+    (rows, comparisons)
+}
+
+/// Regenerates the feed in memory and chains its trips into the fewest blocks.
+pub struct Command {
+    pub min_layover: i64, // Minutes
+    pub output_path: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command { min_layover: 0, output_path: "blocks.csv".to_string() };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--min-layover" => command.min_layover = args.minutes("--min-layover")?,
+                "--output" => command.output_path = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let feed = generate::build(&generate::Options::in_memory(chrono::Local::now().date_naive()), diagnostics)?;
+        let (rows, comparisons) = build(&feed, self.min_layover * 60, diagnostics);
+
+        write_rows(&self.output_path, &rows)?;
+
+        for c in comparisons {
+            println!(
+                "{}: {} trips in {} blocks ({} manual blocks, {} trips conflicting with their manual block)",
+                c.service_id, c.trips, c.blocks, c.manual_blocks, c.manual_conflicts
+            );
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use crate::data::{SERVICES, ScheduleVersion, Service};
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{Calendar, CalendarDate};
+
+/// A schedule version and the dates it covers in the feed.
+#[derive(Clone, Copy)]
+pub struct VersionPeriod<'a> {
+    pub version: &'a ScheduleVersion,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+/// The version in effect on `day`, if the feed covers it.
+pub fn version_on<'a>(periods: &[VersionPeriod<'a>], day: NaiveDate) -> Option<&'a ScheduleVersion> {
+    periods.iter().find(|p| p.start <= day && day <= p.end).map(|p| p.version)
+}
+
+/// A row of `input/special_service.txt`: a date on which one of the SERVICES runs
+/// in addition to the regular calendar, e.g. Weekend service for a weekday event,
+/// or on which the trips of an event service in `input/event_trips.txt` run.
+#[derive(Debug, Deserialize)]
+pub struct SpecialService {
+    pub date: String,
+    pub service: String, // Name in SERVICES or an event service; the version in effect that day picks the service_id
+    pub description: Option<String>,
+}
+
+/// Parses a GTFS date (YYYYMMDD).
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s.trim(), "%Y%m%d").ok()
+}
+
+/// Formats a date as GTFS YYYYMMDD.
+pub fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Parses each version's effective dates, checking that the versions are in date
+/// order and don't overlap. An open-ended version runs until the next one starts.
+fn effective_periods(
+    versions: &[ScheduleVersion],
+    diagnostics: &mut Diagnostics,
+) -> Option<Vec<(NaiveDate, Option<NaiveDate>)>> {
+    let location = Location::file("src/data/mod.rs");
+    let mut parse = |version: &ScheduleVersion, field: &str, value: &str| {
+        let date = parse_date(value);
+        if date.is_none() {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_effective_date",
+                    format!("Schedule version \"{}\" {} \"{}\" is not a YYYYMMDD date", version.name, field, value),
+                )
+                .at(location.clone()),
+            );
+        }
+        date
+    };
+
+    let mut periods = Vec::new();
+    for version in versions {
+        let from = parse(version, "effective_from", version.effective_from);
+        let until = version.effective_until.map(|value| parse(version, "effective_until", value));
+        periods.push((from, until));
+    }
+    let mut periods: Vec<(NaiveDate, Option<NaiveDate>)> = periods
+        .into_iter()
+        .map(|(from, until)| match until {
+            Some(until) => Some((from?, Some(until?))),
+            None => Some((from?, None)),
+        })
+        .collect::<Option<_>>()?;
+
+    let mut valid = true;
+    for i in 0..versions.len() {
+        let (from, until) = periods[i];
+        if until.is_some_and(|until| until < from) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_effective_period",
+                    format!("Schedule version \"{}\" ends before it starts", versions[i].name),
+                )
+                .at(location.clone()),
+            );
+            valid = false;
+        }
+        let Some(&(next_from, _)) = periods.get(i + 1) else { continue };
+        match until {
+            Some(until) if until >= next_from => {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "overlapping_schedule_versions",
+                        format!(
+                            "Schedule version \"{}\" runs until {}, but \"{}\" starts on {}; versions must be in date order without overlapping",
+                            versions[i].name, format_date(until), versions[i + 1].name, format_date(next_from)
+                        ),
+                    )
+                    .at(location.clone()),
+                );
+                valid = false;
+            }
+            Some(_) => {}
+            None => periods[i].1 = next_from.checked_sub_days(Days::new(1)),
+        }
+        if versions[i + 1..].iter().any(|v| v.name == versions[i].name) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "duplicate_schedule_version",
+                    format!("Schedule version name \"{}\" is used more than once", versions[i].name),
+                )
+                .at(location.clone()),
+            );
+            valid = false;
+        }
+    }
+
+    valid.then_some(periods)
+}
+
+/// The service_ids running on `day`, following calendar.txt and the exceptions
+/// in calendar_dates.txt.
+pub fn services_on(calendars: &[Calendar], calendar_dates: &[CalendarDate], day: NaiveDate) -> HashSet<String> {
+    let weekday = day.weekday().num_days_from_monday();
+    let mut services: HashSet<String> = calendars
+        .iter()
+        .filter(|c| {
+            let in_range = parse_date(&c.start_date).is_some_and(|d| d <= day) && parse_date(&c.end_date).is_some_and(|d| day <= d);
+            let days = [c.monday, c.tuesday, c.wednesday, c.thursday, c.friday, c.saturday, c.sunday];
+            in_range && days[weekday as usize] == 1
+        })
+        .map(|c| c.service_id.clone())
+        .collect();
+
+    for exception in calendar_dates.iter().filter(|d| parse_date(&d.date) == Some(day)) {
+        match exception.exception_type {
+            1 => services.insert(exception.service_id.clone()),
+            2 => services.remove(&exception.service_id),
+            _ => false,
+        };
+    }
+    services
+}
+
+/// Works out the dates each schedule version's calendar covers in the feed, in
+/// the same order as `versions`; `None` means the version is left out.
+///
+/// By default every version is in the feed for its whole effective period. With a
+/// rolling window of `rolling_months`, the feed covers today through today +
+/// `rolling_months`, and each version is clipped to that window.
+pub fn feed_periods(
+    versions: &[ScheduleVersion],
+    rolling_months: Option<u32>,
+    today: NaiveDate,
+    diagnostics: &mut Diagnostics,
+) -> Option<Vec<Option<(NaiveDate, NaiveDate)>>> {
+    let location = Location::file("src/data/mod.rs");
+    let periods = effective_periods(versions, diagnostics)?;
+
+    let periods: Vec<Option<(NaiveDate, NaiveDate)>> = match rolling_months {
+        Some(months) => {
+            let window_end = today.checked_add_months(Months::new(months))?;
+            periods
+                .iter()
+                .map(|&(from, until)| {
+                    let (start, end) = (from.max(today), until.map_or(window_end, |u| u.min(window_end)));
+                    (start <= end).then_some((start, end))
+                })
+                .collect()
+        }
+        None => {
+            if let Some((version, _)) = versions.iter().zip(&periods).find(|(_, (_, until))| until.is_none()) {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "no_feed_end_date",
+                        format!(
+                            "Schedule version \"{}\" has no effective_until; set it or run with --rolling-window <months>",
+                            version.name
+                        ),
+                    )
+                    .at(location),
+                );
+                return None;
+            }
+            periods.iter().map(|&(from, until)| until.map(|until| (from, until))).collect()
+        }
+    };
+
+    if periods.iter().all(Option::is_none) {
+        diagnostics.push(
+            Diagnostic::error("empty_feed_period", "No schedule version is in effect during the feed period").at(location),
+        );
+        return None;
+    }
+
+    Some(periods)
+}
+
+/// Warns when the feed has expired or will within `horizon_days`.
+pub fn check_expiry(end: NaiveDate, today: NaiveDate, horizon_days: i64, diagnostics: &mut Diagnostics) {
+    let days_left = (end - today).num_days();
+    if days_left < 0 {
+        diagnostics.push(Diagnostic::warning(
+            "feed_expired",
+            format!("Feed ended on {}, {} day(s) ago", format_date(end), -days_left),
+        ));
+    } else if days_left <= horizon_days {
+        diagnostics.push(Diagnostic::warning(
+            "feed_expiring",
+            format!("Feed ends on {}, in {} day(s) (warning horizon is {} days)", format_date(end), days_left, horizon_days),
+        ));
+    }
+}
+
+/// A row of `input/holidays.txt`. On a holiday the regular services are replaced
+/// by `substitute_service` (a name in SERVICES), or by no service if it is empty.
+#[derive(Debug, Deserialize)]
+pub struct Holiday {
+    pub date: String,
+    pub name: String,
+    #[serde(default)]
+    pub removed_services: Option<String>, // Names in SERVICES, space-separated; empty means those running that day
+    pub substitute_service: Option<String>,
+}
+
+/// Looks up a holiday's service name in SERVICES, reporting it if it isn't one.
+fn holiday_service(name: &str, location: &Location, diagnostics: &mut Diagnostics) -> Option<&'static Service> {
+    let service = SERVICES.iter().find(|s| s.name == name);
+    if service.is_none() {
+        diagnostics.push(
+            Diagnostic::error("unknown_service", format!("Service \"{}\" is not one of the SERVICES in src/data/mod.rs", name))
+                .at(location.clone()),
+        );
+    }
+    service
+}
+
+/// Turns holidays into paired removed/added (`exception_type` 2/1) rows: the
+/// removed services (by default every service that would normally run that day)
+/// are removed, and the substitute is added unless it runs that day anyway.
+/// Holidays outside the feed are skipped.
+pub fn holiday_dates(
+    rows: &[(u64, Holiday)],
+    periods: &[VersionPeriod],
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) -> Vec<CalendarDate> {
+    let mut dates = Vec::new();
+    for (line, row) in rows {
+        let location = Location::line(path, *line);
+        let Some(day) = parse_date(&row.date) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_date", format!("{} date \"{}\" is not a YYYYMMDD date", row.name, row.date))
+                    .at(location),
+            );
+            continue;
+        };
+        let substitute = match &row.substitute_service {
+            Some(name) => match holiday_service(name, &location, diagnostics) {
+                Some(service) => Some(service),
+                None => continue,
+            },
+            None => None,
+        };
+        let removed = match &row.removed_services {
+            Some(names) => {
+                let services: Vec<Option<&Service>> =
+                    names.split_whitespace().map(|name| holiday_service(name, &location, diagnostics)).collect();
+                match services.into_iter().collect::<Option<Vec<&Service>>>() {
+                    Some(services) => Some(services),
+                    None => continue,
+                }
+            }
+            None => None,
+        };
+        let Some(version) = version_on(periods, day) else {
+            continue;
+        };
+
+        let weekday = day.weekday().num_days_from_monday() as usize;
+        for service in &SERVICES {
+            let runs = service.days[weekday] == 1;
+            let removed = removed.as_ref().map_or(runs, |removed| removed.iter().any(|s| s.name == service.name));
+            let substituted = substitute.is_some_and(|s| s.name == service.name);
+            let exception_type = match (removed, substituted) {
+                (true, false) => 2,          // Removed
+                (false, true) if !runs => 1, // Added
+                _ => continue,
+            };
+            dates.push(CalendarDate { service_id: version.service_id(service.name), date: format_date(day), exception_type });
+        }
+    }
+    dates
+}
+
+/// Turns special service days into added-service (`exception_type` 1) rows.
+/// `event_services` are the services of the event trips, which only run on the
+/// dates listed for them.
+pub fn special_service_dates(
+    rows: &[(u64, SpecialService)],
+    periods: &[VersionPeriod],
+    event_services: &[&str],
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) -> Vec<CalendarDate> {
+    let mut dates = Vec::new();
+    for (line, row) in rows {
+        let location = Location::line(path, *line);
+        let Some(day) = parse_date(&row.date) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_date", format!("date \"{}\" is not a YYYYMMDD date", row.date)).at(location),
+            );
+            continue;
+        };
+        let service = SERVICES.iter().find(|s| s.name == row.service);
+        if service.is_none() && !event_services.contains(&row.service.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "unknown_service",
+                    format!("Service \"{}\" is not one of the SERVICES in src/data/mod.rs or in input/event_trips.txt", row.service),
+                )
+                .at(location),
+            );
+            continue;
+        }
+        let Some(version) = version_on(periods, day) else {
+            diagnostics.push(
+                Diagnostic::warning("special_service_outside_feed", format!("{} is outside the feed period; left out", row.date))
+                    .at(location),
+            );
+            continue;
+        };
+        if service.is_some_and(|s| s.days[day.weekday().num_days_from_monday() as usize] == 1) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "special_service_already_scheduled",
+                    format!("{} service already runs on {} ({})", row.service, row.date, day.weekday()),
+                )
+                .at(location),
+            );
+            continue;
+        }
+
+        dates.push(CalendarDate {
+            service_id: version.service_id(&row.service),
+            date: format_date(day),
+            exception_type: 1, // Added
+        });
+    }
+    dates
+}
+
+/// Reports service_id/date pairs listed more than once, e.g. a holiday that
+/// removes a service on the same day a special event adds it.
+pub fn check_calendar_dates(dates: &[CalendarDate], diagnostics: &mut Diagnostics) {
+    let mut seen = HashSet::new();
+    for date in dates {
+        if !seen.insert((&date.service_id, &date.date)) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "duplicate_calendar_date",
+                    format!("{} has more than one exception on {}", date.service_id, date.date),
+                )
+                .at(Location::file("gtfs/calendar_dates.txt")),
+            );
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+use crate::calendar::parse_date;
+use crate::time::parse_time;
+
+/// A subcommand's arguments, read one at a time. Every error message ends with the
+/// usage text.
+pub struct Args {
+    args: std::vec::IntoIter<String>,
+    usage: &'static str,
+}
+
+impl Args {
+    pub fn new(args: Vec<String>, usage: &'static str) -> Args {
+        Args { args: args.into_iter(), usage }
+    }
+
+    /// The next argument; `-h` and `--help` stop with the usage text.
+    pub fn next_arg(&mut self) -> Result<Option<String>, String> {
+        match self.args.next() {
+            Some(arg) if arg == "-h" || arg == "--help" => Err(self.usage.to_string()),
+            arg => Ok(arg),
+        }
+    }
+
+    /// The value following the option `name`.
+    pub fn value(&mut self, name: &str) -> Result<String, String> {
+        self.args.next().ok_or_else(|| self.error(&format!("{} needs a value", name)))
+    }
+
+    /// The value following `name`, parsed; `what` names it in the error, e.g. "year".
+    pub fn parse<T: FromStr>(&mut self, name: &str, what: &str) -> Result<T, String> {
+        let value = self.value(name)?;
+        value.parse().map_err(|_| self.error(&format!("Invalid {}: {}", what, value)))
+    }
+
+    /// A number of minutes, which can't be negative.
+    pub fn minutes(&mut self, name: &str) -> Result<i64, String> {
+        let minutes: i64 = self.parse(name, "number of minutes")?;
+        if minutes < 0 {
+            return Err(self.error(&format!("Invalid number of minutes: {}", minutes)));
+        }
+        Ok(minutes)
+    }
+
+    /// A YYYYMMDD date.
+    pub fn date(&mut self, name: &str) -> Result<NaiveDate, String> {
+        let value = self.value(name)?;
+        parse_date(&value).ok_or_else(|| self.error(&format!("Invalid date: {}", value)))
+    }
+
+    /// A time of day, as seconds after midnight.
+    pub fn time(&mut self, name: &str) -> Result<i64, String> {
+        let value = self.value(name)?;
+        parse_time(&value).ok_or_else(|| self.error(&format!("Invalid time: {}", value)))
+    }
+
+    pub fn unknown(&self, arg: &str) -> String {
+        self.error(&format!("Unknown argument {}", arg))
+    }
+
+    pub fn error(&self, message: &str) -> String {
+        format!("{}\n{}", message, self.usage)
+    }
+}
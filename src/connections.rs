@@ -0,0 +1,301 @@
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+
+use crate::calendar::{format_date, services_on};
+use crate::cli::Args;
+use crate::diagnostics::Diagnostics;
+use crate::output::write_rows;
+use crate::partner::{self, PartnerFeed, PartnerStopTime};
+use crate::time::{format_time, parse_time};
+use crate::transfers::{self, PartnerConfig};
+
+/// Irvine Station, where Irvine Connect meets Metrolink.
+pub const IRVINE_STATION_DOCK_4: &str = "157583";
+
+/// A bus or train stopping at the station.
+pub(crate) struct Event<'a> {
+    pub(crate) trip_id: &'a str,
+    pub(crate) time: i64, // Seconds after midnight
+    pub(crate) walk: i64, // Seconds between this stop and the bus stop
+}
+
+/// One bus arrival or departure against one train line and direction.
+#[derive(Debug, Serialize)]
+pub struct ConnectionRow {
+    pub day_type: String, // Our service_ids running that day
+    pub date: String,
+    pub direction: &'static str, // "bus_to_train" or "train_to_bus"
+    pub bus_trip_id: String,
+    pub bus_time: String,
+    pub train_route_id: String,
+    pub train_direction: String, // direction_id, or trip_headsign if the feed has none
+    pub train_trip_id: Option<String>, // The train making the connection within the window
+    pub train_time: Option<String>,
+    pub wait_minutes: Option<i64>, // Excluding the walk
+    pub missed_trip_id: Option<String>, // The closest train the connection just misses
+    pub missed_time: Option<String>,
+    pub missed_by_minutes: Option<i64>,
+}
+
+/// Arrivals (stop times after a trip's first) and departures (before its last) of
+/// trips running on `services`, at `stops` (stop_id → walking time).
+pub(crate) fn events<'a>(
+    feed: &'a PartnerFeed,
+    services: &HashSet<String>,
+    stops: &HashMap<&str, i64>,
+) -> (Vec<Event<'a>>, Vec<Event<'a>>) {
+    let running: HashSet<&str> = feed
+        .trips
+        .iter()
+        .filter(|t| services.contains(&t.service_id))
+        .map(|t| t.trip_id.as_str())
+        .collect();
+
+    let mut by_trip: HashMap<&str, Vec<&PartnerStopTime>> = HashMap::new();
+    for st in feed.stop_times.iter().filter(|st| running.contains(st.trip_id.as_str())) {
+        by_trip.entry(&st.trip_id).or_default().push(st);
+    }
+
+    let (mut arrivals, mut departures) = (Vec::new(), Vec::new());
+    for (trip_id, mut stop_times) in by_trip {
+        stop_times.sort_by_key(|st| st.stop_sequence);
+        let last = stop_times.len() - 1;
+        for (i, st) in stop_times.iter().enumerate() {
+            let Some(&walk) = stops.get(st.stop_id.as_str()) else { continue };
+            let arrival = st.arrival_time.as_deref().or(st.departure_time.as_deref()).and_then(parse_time);
+            let departure = st.departure_time.as_deref().or(st.arrival_time.as_deref()).and_then(parse_time);
+            if let (true, Some(time)) = (i > 0, arrival) {
+                arrivals.push(Event { trip_id, time, walk });
+            }
+            if let (true, Some(time)) = (i < last, departure) {
+                departures.push(Event { trip_id, time, walk });
+            }
+        }
+    }
+    arrivals.sort_by_key(|e| e.time);
+    departures.sort_by_key(|e| e.time);
+    (arrivals, departures)
+}
+
+/// Train events grouped by (route_id, direction).
+pub(crate) type Lines<'a> = BTreeMap<(String, String), Vec<Event<'a>>>;
+
+/// Time to spare for a connection between a bus and a train; negative means missed.
+type Slack = fn(&Event, &Event) -> i64;
+
+pub(crate) fn by_line<'a>(feed: &PartnerFeed, events: Vec<Event<'a>>) -> Lines<'a> {
+    let lines: HashMap<&str, (String, String)> = feed
+        .trips
+        .iter()
+        .map(|t| {
+            let direction = match (t.direction_id, &t.trip_headsign) {
+                (Some(id), _) => id.to_string(),
+                (None, Some(headsign)) => headsign.clone(),
+                (None, None) => String::new(),
+            };
+            (t.trip_id.as_str(), (t.route_id.clone(), direction))
+        })
+        .collect();
+
+    let mut groups = Lines::new();
+    for event in events {
+        groups.entry(lines[event.trip_id].clone()).or_default().push(event);
+    }
+    groups
+}
+
+/// Compares bus and train times at the station for each day type: the first date
+/// on or after `from` on which each distinct set of our services runs, over a week.
+///
+/// A bus arrival connects to the first train departing after the walk, and a bus
+/// departure to the last train arriving in time for it; both only within `window`
+/// seconds. The train just missed, if also within the window, is reported too.
+pub fn analyze(
+    ours: &PartnerFeed,
+    partner: &PartnerFeed,
+    config: &PartnerConfig,
+    stop_id: &str,
+    from: NaiveDate,
+    window: i64,
+) -> Result<Vec<ConnectionRow>, String> {
+    let hub = ours
+        .stops
+        .iter()
+        .find(|s| s.stop_id == stop_id)
+        .ok_or(format!("Stop {} is not in the feed", stop_id))?;
+    let (Some(lat), Some(lon)) = (hub.stop_lat, hub.stop_lon) else {
+        return Err(format!("Stop {} has no location", stop_id));
+    };
+    let station: HashMap<&str, i64> = config
+        .stops_within_walk(lat, lon, &partner.stops)
+        .into_iter()
+        .map(|(s, walk)| (s.stop_id.as_str(), walk as i64))
+        .collect();
+    if station.is_empty() {
+        return Err(format!("No {} stops are within {} m of stop {}", config.feed_id, config.max_walking_distance, stop_id));
+    }
+    let hub_stops = HashMap::from([(stop_id, 0)]);
+
+    // Every line calling at the station, so days a line doesn't run still show up
+    let all_services: HashSet<String> = partner.trips.iter().map(|t| t.service_id.clone()).collect();
+    let (all_arrivals, all_departures) = events(partner, &all_services, &station);
+    let all_lines: BTreeSet<(String, String)> =
+        by_line(partner, all_arrivals).into_keys().chain(by_line(partner, all_departures).into_keys()).collect();
+
+    // Day types in the week from `from`, each with the first date it runs
+    let mut day_types: Vec<(Vec<String>, NaiveDate)> = Vec::new();
+    for offset in 0..7 {
+        let date = from.checked_add_days(Days::new(offset)).ok_or("Date out of range")?;
+        let mut services: Vec<String> = services_on(&ours.calendars, &ours.calendar_dates, date).into_iter().collect();
+        services.sort();
+        if !services.is_empty() && !day_types.iter().any(|(s, _)| *s == services) {
+            day_types.push((services, date));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for (services, date) in day_types {
+        let (bus_arrivals, bus_departures) = events(ours, &services.iter().cloned().collect(), &hub_stops);
+        let partner_services = services_on(&partner.calendars, &partner.calendar_dates, date);
+        let (train_arrivals, train_departures) = events(partner, &partner_services, &station);
+
+        let cases: [(&str, Vec<Event>, Lines, Slack); 2] = [
+            ("bus_to_train", bus_arrivals, by_line(partner, train_departures), |bus, train| {
+                train.time - (bus.time + train.walk)
+            }),
+            ("train_to_bus", bus_departures, by_line(partner, train_arrivals), |bus, train| {
+                bus.time - (train.time + train.walk)
+            }),
+        ];
+        for (direction, buses, lines, slack) in cases {
+            for bus in &buses {
+                for line @ (route_id, train_direction) in &all_lines {
+                    let trains = lines.get(line).map_or(&[][..], |t| &t[..]);
+                    let made = trains
+                        .iter()
+                        .filter(|t| (0..=window).contains(&slack(bus, t)))
+                        .min_by_key(|t| slack(bus, t));
+                    let missed = trains
+                        .iter()
+                        .filter(|t| (-window..0).contains(&slack(bus, t)))
+                        .max_by_key(|t| slack(bus, t));
+                    rows.push(ConnectionRow {
+                        day_type: services.join(" "),
+                        date: format_date(date),
+                        direction,
+                        bus_trip_id: bus.trip_id.to_string(),
+                        bus_time: format_time(bus.time),
+                        train_route_id: route_id.clone(),
+                        train_direction: train_direction.clone(),
+                        train_trip_id: made.map(|t| t.trip_id.to_string()),
+                        train_time: made.map(|t| format_time(t.time)),
+                        wait_minutes: made.map(|t| slack(bus, t) / 60),
+                        missed_trip_id: missed.map(|t| t.trip_id.to_string()),
+                        missed_time: missed.map(|t| format_time(t.time)),
+                        missed_by_minutes: missed.map(|t| (-slack(bus, t) + 59) / 60),
+                    });
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Totals for one day type and direction.
+pub struct Summary {
+    pub day_type: String,
+    pub date: String,
+    pub direction: &'static str,
+    pub total: usize,   // Bus arrivals/departures times train lines
+    pub made: usize,    // Of those, connections within the window
+    pub missed: usize,  // No connection within the window, but a train just missed
+    pub wait_minutes: i64,
+}
+
+impl Summary {
+    pub fn average_wait(&self) -> f64 {
+        if self.made == 0 { 0.0 } else { self.wait_minutes as f64 / self.made as f64 }
+    }
+}
+
+pub fn summarize(rows: &[ConnectionRow]) -> Vec<Summary> {
+    let mut summaries: Vec<Summary> = Vec::new();
+    for row in rows {
+        let index = match summaries.iter().position(|s| s.day_type == row.day_type && s.direction == row.direction) {
+            Some(index) => index,
+            None => {
+                summaries.push(Summary {
+                    day_type: row.day_type.clone(),
+                    date: row.date.clone(),
+                    direction: row.direction,
+                    total: 0,
+                    made: 0,
+                    missed: 0,
+                    wait_minutes: 0,
+                });
+                summaries.len() - 1
+            }
+        };
+        let summary = &mut summaries[index];
+        summary.total += 1;
+        if let Some(wait) = row.wait_minutes {
+            summary.made += 1;
+            summary.wait_minutes += wait;
+        } else if row.missed_trip_id.is_some() {
+            summary.missed += 1;
+        }
+    }
+    summaries
+}
+
+/// Compares the generated feed in `gtfs/` with a partner's trains at the station.
+pub struct Command {
+    pub date: NaiveDate,
+    pub partner: Option<String>, // feed_id in input/partner_feeds.txt; the first one if not given
+    pub stop_id: String,
+    pub window: i64, // Minutes
+    pub output_path: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command {
+            date: chrono::Local::now().date_naive(),
+            partner: None,
+            stop_id: IRVINE_STATION_DOCK_4.to_string(),
+            window: 30,
+            output_path: "connections.csv".to_string(),
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--date" => command.date = args.date("--date")?,
+                "--partner" => command.partner = Some(args.value("--partner")?),
+                "--stop" => command.stop_id = args.value("--stop")?,
+                "--window" => command.window = args.minutes("--window")?,
+                "--output" => command.output_path = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let config = transfers::partner_config(self.partner.as_deref(), diagnostics)?;
+        let ours = partner::load("gtfs", diagnostics)?;
+        let theirs = partner::load(&config.path, diagnostics)?;
+        let rows = analyze(&ours, &theirs, &config, &self.stop_id, self.date, self.window * 60)?;
+
+        write_rows(&self.output_path, &rows)?;
+
+        for s in summarize(&rows) {
+            println!(
+                "{} ({}), {}: {} of {} connections within {} min (average wait {:.1} min), {} missed",
+                s.day_type, s.date, s.direction, s.made, s.total, self.window, s.average_wait(), s.missed
+            );
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use serde::Deserialize;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::time::{format_time, parse_time};
+
+mod irvine_connect;
+
+// Services
+// Day-of-week patterns, Monday to Sunday. Every schedule version has its own copy
+// of each service (see ScheduleVersion::service_id). Extra event trips have their
+// own services instead, listed in input/event_trips.txt (see EventTrip).
+pub struct Service {
+    pub name: &'static str,
+    pub days: [u8; 7],
+}
+
+pub const SERVICES: [Service; 2] = [
+    Service { name: "Weekday", days: [1, 1, 1, 1, 1, 0, 0] },
+    Service { name: "Weekend", days: [0, 0, 0, 0, 0, 1, 1] },
+];
+
+/// A published timetable and the dates it is in effect (YYYYMMDD, inclusive).
+/// `effective_until: None` means until the next version starts; for the last
+/// version it means no announced end, and the feed needs --rolling-window.
+pub struct ScheduleVersion {
+    pub name: &'static str, // Appended to service_ids ("Weekday_2026Fall"); empty keeps them as they are
+    pub effective_from: &'static str,
+    pub effective_until: Option<&'static str>,
+    pub routes: &'static [fn(&ScheduleVersion, &mut Diagnostics) -> RouteSchedule],
+}
+
+impl ScheduleVersion {
+    /// The service_id this version uses for one of the SERVICES.
+    pub fn service_id(&self, service: &str) -> String {
+        if self.name.is_empty() {
+            service.to_string()
+        } else {
+            format!("{}_{}", service, self.name)
+        }
+    }
+}
+
+// Schedule Versions
+// In date order, without overlapping. To publish an upcoming timetable alongside
+// the current one, give the current version an end date, copy its route module
+// (e.g. irvine_connect_2026_fall.rs) and add a version for it here:
+// ScheduleVersion { name: "2026Fall", effective_from: "20260824", effective_until: None, routes: &[irvine_connect_2026_fall::route] },
+pub const VERSIONS: [ScheduleVersion; 1] = [
+    ScheduleVersion {
+        name: "",
+        effective_from: "20250101",
+        effective_until: Some("20261231"),
+        routes: &[irvine_connect::route],
+    },
+];
+
+pub struct TripInput {
+    pub trip_id: String,
+    pub raw_index: usize, // Position in the service's RawTrip vector, for diagnostics
+    pub route_id: &'static str,
+    pub service_id: String,
+    pub block_id: String,
+    pub wheelchair_accessible: u8,
+    pub bikes_allowed: u8,
+    pub pattern: Pattern,
+    pub stops: Vec<(u32, Option<String>)>,
+}
+
+/// Everything needed to generate one route. The ordered stop list is read from
+/// `input/routes/<route_id>/stop_id_sequence.txt`.
+pub struct RouteSchedule {
+    pub route_id: &'static str,
+    pub shape_id: &'static str,
+    pub timepoints: Vec<Timepoint>,
+    pub offsets: Vec<i64>, // Minutes from the first timepoint, as in the route's OFFSETS
+    pub loop_length: usize, // Number of stops in one loop of the stop sequence
+    pub headsigns: &'static [(usize, &'static str)], // (index within loop, headsign), each applies until the next
+    pub trips: Vec<TripInput>,
+}
+
+/// A timepoint stop. `occurrence` picks which visit to the stop in
+/// `stop_id_sequence.txt` the timepoint refers to (1 = first), for stops the
+/// sequence passes more than once. Without it the matcher takes the only visit
+/// between the neighbouring timepoints and rejects the trip if there are several.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timepoint {
+    pub stop_id: u32,
+    pub occurrence: Option<usize>,
+}
+
+impl Timepoint {
+    pub const fn stop(stop_id: u32) -> Timepoint {
+        Timepoint { stop_id, occurrence: None }
+    }
+
+    pub const fn nth(stop_id: u32, occurrence: usize) -> Timepoint {
+        Timepoint { stop_id, occurrence: Some(occurrence) }
+    }
+}
+
+/// A run between two of a route's timepoints (inclusive indices into its TIMEPOINTS).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub first: usize,
+    pub last: usize,
+    pub headsign: Option<&'static str>, // Used for the whole trip instead of the route's headsigns
+}
+
+// Vehicle Types
+// wheelchair_accessible / bikes_allowed use the GTFS trips.txt values:
+// 0 = No info, 1 = Yes, 2 = No
+struct VehicleType {
+    name: &'static str,
+    wheelchair_accessible: u8,
+    bikes_allowed: u8,
+}
+
+const VEHICLE_TYPES: [VehicleType; 1] = [
+    VehicleType { name: "bus", wheelchair_accessible: 1, bikes_allowed: 1 }, // Low floor with ramp and front bike rack
+];
+
+// Vehicle type used by blocks not listed in BLOCKS
+const DEFAULT_VEHICLE_TYPE: &str = "bus";
+
+// Per-block vehicle assignment. Set wheelchair_accessible / bikes_allowed
+// to override the vehicle type's value for that block only.
+struct BlockConfig {
+    block_id: &'static str,
+    vehicle_type: &'static str,
+    wheelchair_accessible: Option<u8>,
+    bikes_allowed: Option<u8>,
+}
+
+const BLOCKS: [BlockConfig; 0] = [
+    // BlockConfig { block_id: "0520", vehicle_type: "bus", wheelchair_accessible: None, bikes_allowed: Some(2) },
+];
+
+/// Resolves (wheelchair_accessible, bikes_allowed) for a block, or the name of
+/// the vehicle type that could not be found.
+fn block_attributes(block_id: &str) -> Result<(u8, u8), &'static str> {
+    let block = BLOCKS.iter().find(|b| b.block_id == block_id);
+    let type_name = block.map_or(DEFAULT_VEHICLE_TYPE, |b| b.vehicle_type);
+
+    let vehicle = VEHICLE_TYPES
+        .iter()
+        .find(|v| v.name == type_name)
+        .ok_or(type_name)?;
+
+    let wheelchair_accessible = block
+        .and_then(|b| b.wheelchair_accessible)
+        .unwrap_or(vehicle.wheelchair_accessible);
+    let bikes_allowed = block
+        .and_then(|b| b.bikes_allowed)
+        .unwrap_or(vehicle.bikes_allowed);
+
+    Ok((wheelchair_accessible, bikes_allowed))
+}
+
+pub struct RawTrip {
+    pub bus_id: u32,
+    pub block_id: &'static str,
+    pub start_time: &'static str,
+    pub pattern: Pattern,
+}
+
+pub fn get_routes(version: &ScheduleVersion, diagnostics: &mut Diagnostics) -> Vec<RouteSchedule> {
+    version.routes.iter().map(|route| route(version, diagnostics)).collect()
+}
+
+/// Times at the timepoints a pattern runs through, for a trip leaving its first
+/// timepoint at `start` (seconds).
+pub fn timepoint_times(start: i64, pattern: Pattern, timepoints: &[Timepoint], offsets: &[i64]) -> Vec<(u32, Option<String>)> {
+    // The start time is the time at pattern.first, so the time at idx is
+    // start + (offsets[idx] - offsets[pattern.first])
+    let base_offset = offsets[pattern.first];
+    (pattern.first..=pattern.last)
+        .map(|idx| (timepoints[idx].stop_id, Some(format_time(start + (offsets[idx] - base_offset) * 60))))
+        .collect()
+}
+
+pub fn process_trips(
+    trips: &mut Vec<TripInput>,
+    raw_trips: Vec<RawTrip>,
+    service_id: &str,
+    route_id: &'static str,
+    timepoints: &[Timepoint],
+    offsets: &[i64],
+    diagnostics: &mut Diagnostics,
+) {
+    if timepoints.len() != offsets.len() {
+        diagnostics.push(Diagnostic::error(
+            "timepoint_offset_mismatch",
+            format!("Route {} has {} TIMEPOINTS but {} OFFSETS", route_id, timepoints.len(), offsets.len()),
+        ));
+        return;
+    }
+
+    for (i, trip) in raw_trips.into_iter().enumerate() {
+        let location = Location::raw_trip(route_id, service_id, i);
+
+        let Some(start) = parse_time(trip.start_time) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_start_time", format!("start_time \"{}\" is not a valid HH:MM time", trip.start_time))
+                    .at(location),
+            );
+            continue;
+        };
+        
+        // Define range based on pattern
+        let (min_idx, max_idx) = (trip.pattern.first, trip.pattern.last);
+        if min_idx >= max_idx || max_idx >= offsets.len() {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_pattern",
+                    format!("Pattern runs from timepoint {} to {}, but the route has timepoints 0-{}", min_idx, max_idx, offsets.len() - 1),
+                )
+                .at(location),
+            );
+            continue;
+        }
+
+        let stops = timepoint_times(start, trip.pattern, timepoints, offsets);
+
+        let (wheelchair_accessible, bikes_allowed) = match block_attributes(trip.block_id) {
+            Ok(attributes) => attributes,
+            Err(type_name) => {
+                diagnostics.push(
+                    Diagnostic::error("unknown_vehicle_type", format!("Block {} uses unknown vehicle type {}", trip.block_id, type_name))
+                        .at(location),
+                );
+                continue;
+            }
+        };
+
+        trips.push(TripInput {
+            trip_id: format!("{}_{}_{}_{}", route_id, service_id.to_lowercase(), trip.bus_id, i + 1),
+            raw_index: i,
+            route_id,
+            service_id: service_id.to_string(),
+            block_id: trip.block_id.to_string(),
+            wheelchair_accessible,
+            bikes_allowed,
+            pattern: trip.pattern,
+            stops,
+        });
+    }
+}
+
+/// A row of `input/event_trips.txt`: an extra trip, e.g. for an event at the Great
+/// Park, that runs only on the dates its service is listed for in
+/// `input/special_service.txt`. The trip runs the route's timepoints from
+/// `first_timepoint` to `last_timepoint` (indices into its TIMEPOINTS).
+#[derive(Debug, Deserialize)]
+pub struct EventTrip {
+    pub service: String, // Not one of SERVICES; each version has its own service_id for it
+    pub route_id: String,
+    pub bus_id: u32,
+    #[serde(default)]
+    pub block_id: Option<String>,
+    pub start_time: String,
+    pub first_timepoint: usize,
+    pub last_timepoint: usize,
+}
+
+/// The services of the event trips, in the order first listed.
+pub fn event_services(rows: &[(u64, EventTrip)]) -> Vec<&str> {
+    let mut services: Vec<&str> = Vec::new();
+    for (_, row) in rows {
+        if !services.contains(&row.service.as_str()) {
+            services.push(&row.service);
+        }
+    }
+    services
+}
+
+/// Adds the event trips to the schedules of their routes in one version. Rows
+/// that can't be used are reported against their line and left out.
+pub fn add_event_trips(
+    schedules: &mut [RouteSchedule],
+    version: &ScheduleVersion,
+    rows: &[(u64, EventTrip)],
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    for (i, (line, row)) in rows.iter().enumerate() {
+        let location = Location::line(path, *line);
+        if SERVICES.iter().any(|s| s.name == row.service) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_event_trip",
+                    format!("{} is a regular service; list its extra dates in input/special_service.txt instead", row.service),
+                )
+                .at(location),
+            );
+            continue;
+        }
+        let Some(route) = schedules.iter_mut().find(|r| r.route_id == row.route_id) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_event_trip", format!("Route {} has no schedule in src/data", row.route_id)).at(location),
+            );
+            continue;
+        };
+        let Some(start) = parse_time(&row.start_time) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_event_trip", format!("start_time \"{}\" is not a valid HH:MM time", row.start_time))
+                    .at(location),
+            );
+            continue;
+        };
+        let (first, last) = (row.first_timepoint, row.last_timepoint);
+        if first >= last || last >= route.offsets.len() {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_event_trip",
+                    format!("Trip runs from timepoint {} to {}, but the route has timepoints 0-{}", first, last, route.offsets.len() - 1),
+                )
+                .at(location),
+            );
+            continue;
+        }
+        let block_id = row.block_id.clone().unwrap_or_default();
+        let (wheelchair_accessible, bikes_allowed) = match block_attributes(&block_id) {
+            Ok(attributes) => attributes,
+            Err(type_name) => {
+                diagnostics.push(
+                    Diagnostic::error("unknown_vehicle_type", format!("Block {} uses unknown vehicle type {}", block_id, type_name))
+                        .at(location),
+                );
+                continue;
+            }
+        };
+
+        let service_id = version.service_id(&row.service);
+        let pattern = Pattern { first, last, headsign: None };
+        route.trips.push(TripInput {
+            trip_id: format!("{}_{}_{}_{}", route.route_id, service_id.to_lowercase(), row.bus_id, i + 1),
+            raw_index: i,
+            route_id: route.route_id,
+            service_id,
+            block_id,
+            wheelchair_accessible,
+            bikes_allowed,
+            pattern,
+            stops: timepoint_times(start, pattern, &route.timepoints, &route.offsets),
+        });
+    }
+}
@@ -0,0 +1,335 @@
+use geo::{HaversineDistance, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::blocks::{self, BlockRow};
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate;
+use crate::gtfs::RawStop;
+use crate::input::read_csv_lines;
+use crate::output::{METERS_PER_MILE, round, write_rows};
+use crate::time::{format_time, parse_time};
+
+// Deadhead speed used when a garage doesn't set one (mph)
+pub const DEFAULT_DEADHEAD_SPEED: f64 = 20.0;
+
+// Road distance per straight-line distance, used when a garage doesn't set one
+pub const DEFAULT_CIRCUITY: f64 = 1.3;
+
+/// A row of `input/garages.txt`: a yard buses pull out from and pull in to.
+#[derive(Debug, Deserialize)]
+pub struct Garage {
+    pub garage_id: String,
+    pub garage_name: String,
+    pub garage_lat: f64,
+    pub garage_lon: f64,
+    #[serde(default)]
+    pub speed_mph: Option<f64>,
+    #[serde(default)]
+    pub circuity: Option<f64>,
+}
+
+impl Garage {
+    /// Road distance (meters) and time (seconds, rounded up to a minute) to a point.
+    fn deadhead(&self, to: Point) -> (f64, i64) {
+        let meters = Point::new(self.garage_lon, self.garage_lat).haversine_distance(&to)
+            * self.circuity.unwrap_or(DEFAULT_CIRCUITY);
+        let hours = meters / METERS_PER_MILE / self.speed_mph.unwrap_or(DEFAULT_DEADHEAD_SPEED);
+        (meters, (hours * 60.0).ceil() as i64 * 60)
+    }
+}
+
+/// Loads the garages, reporting those whose location or speed can't be used.
+pub fn load(path: &str, diagnostics: &mut Diagnostics) -> Result<Vec<Garage>, Box<dyn Error>> {
+    let garages: Vec<(u64, Garage)> = read_csv_lines(path, diagnostics)?;
+    if garages.is_empty() {
+        return Err(format!("{} has no garages", path).into());
+    }
+    for (line, garage) in &garages {
+        if !(-90.0..=90.0).contains(&garage.garage_lat)
+            || !(-180.0..=180.0).contains(&garage.garage_lon)
+            || garage.speed_mph.is_some_and(|s| s <= 0.0)
+            || garage.circuity.is_some_and(|c| c < 1.0)
+        {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid_garage",
+                    "garage_lat and garage_lon must be a location, speed_mph positive and circuity at least 1",
+                )
+                .at(Location::line(path, *line)),
+            );
+        }
+    }
+    Ok(garages.into_iter().map(|(_, g)| g).collect())
+}
+
+/// Revenue and non-revenue time of one manual block, with its pull-out from and
+/// pull-in to the nearest garages.
+#[derive(Debug, Serialize)]
+pub struct BlockDeadheads {
+    pub service_id: String,
+    pub block_id: String,
+    pub pull_out_garage_id: String,
+    pub pull_out_time: String, // Leaves the garage; 00:00:00 if that would be before midnight
+    pub pull_out_stop_id: String,
+    pub pull_out_minutes: i64,
+    pub pull_out_miles: f64,
+    pub first_trip_id: String,
+    pub last_trip_id: String,
+    pub pull_in_stop_id: String,
+    pub pull_in_minutes: i64,
+    pub pull_in_miles: f64,
+    pub pull_in_time: String, // Arrives at the garage
+    pub pull_in_garage_id: String,
+    pub revenue_minutes: i64,
+    pub layover_minutes: i64,     // Between the block's trips
+    pub non_revenue_minutes: i64, // Deadheads and layovers
+    pub non_revenue_percent: f64, // Of the time from pull-out to pull-in
+}
+
+/// Deadheads of each manual block (`rows` grouped by `manual_block_id`; trips without
+/// a block are skipped). A block pulls out from the garage closest to its first stop
+/// and pulls in to the one closest to its last stop, both by road distance. A block
+/// whose pull-out would leave before midnight can't be given in service-day times, so
+/// it is reported and left out.
+pub fn compute(garages: &[Garage], rows: &[BlockRow], stops: &[RawStop], diagnostics: &mut Diagnostics) -> Vec<BlockDeadheads> {
+    let locations: HashMap<&str, Point> =
+        stops.iter().map(|s| (s.stop_id.as_str(), Point::new(s.stop_lon, s.stop_lat))).collect();
+    let nearest = |stop_id: &str| {
+        let point = locations.get(stop_id)?;
+        garages.iter().map(|g| (g, g.deadhead(*point))).min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))
+    };
+
+    let mut blocks: BTreeMap<(&str, &str), Vec<&BlockRow>> = BTreeMap::new();
+    for row in rows.iter().filter(|r| !r.manual_block_id.is_empty()) {
+        blocks.entry((&row.service_id, &row.manual_block_id)).or_default().push(row);
+    }
+
+    let mut deadheads = Vec::new();
+    for ((service_id, block_id), mut block) in blocks {
+        block.sort_by_key(|r| parse_time(&r.start_time));
+        let (first, last) = (block[0], block[block.len() - 1]);
+        let (Some((out_garage, (out_meters, out_secs))), Some((in_garage, (in_meters, in_secs)))) =
+            (nearest(&first.start_stop_id), nearest(&last.end_stop_id))
+        else {
+            continue;
+        };
+        let spans: Vec<(i64, i64)> = block
+            .iter()
+            .map(|r| {
+                let start = parse_time(&r.start_time).unwrap_or(0);
+                (start, parse_time(&r.end_time).unwrap_or(start))
+            })
+            .collect();
+        let revenue: i64 = spans.iter().map(|(start, end)| end - start).sum();
+        let layover: i64 = spans.windows(2).map(|pair| (pair[1].0 - pair[0].1).max(0)).sum();
+        let (pull_out, pull_in) = (spans[0].0 - out_secs, spans[spans.len() - 1].1 + in_secs);
+        if pull_out < 0 {
+            diagnostics.push(
+                Diagnostic::error(
+                    "pull_out_before_midnight",
+                    format!(
+                        "Block {} would pull out of {} {} min before midnight to start at {}; its deadheads are left out",
+                        block_id, out_garage.garage_id, -pull_out / 60, first.start_time
+                    ),
+                )
+                .at(Location::trip(&first.trip_id)),
+            );
+            continue;
+        }
+        let non_revenue = out_secs + layover + in_secs;
+
+        deadheads.push(BlockDeadheads {
+            service_id: service_id.to_string(),
+            block_id: block_id.to_string(),
+            pull_out_garage_id: out_garage.garage_id.clone(),
+            pull_out_time: format_time(pull_out),
+            pull_out_stop_id: first.start_stop_id.clone(),
+            pull_out_minutes: out_secs / 60,
+            pull_out_miles: round(out_meters / METERS_PER_MILE),
+            first_trip_id: first.trip_id.clone(),
+            last_trip_id: last.trip_id.clone(),
+            pull_in_stop_id: last.end_stop_id.clone(),
+            pull_in_minutes: in_secs / 60,
+            pull_in_miles: round(in_meters / METERS_PER_MILE),
+            pull_in_time: format_time(pull_in),
+            pull_in_garage_id: in_garage.garage_id.clone(),
+            revenue_minutes: revenue / 60,
+            layover_minutes: layover / 60,
+            non_revenue_minutes: non_revenue / 60,
+            non_revenue_percent: round(non_revenue as f64 / (revenue + non_revenue).max(1) as f64 * 100.0),
+        });
+    }
+    deadheads
+}
+
+/// A row of `ops_locations.txt` (GTFS Operational Data Standard).
+#[derive(Debug, Serialize)]
+struct OpsLocation<'a> {
+    ops_location_id: &'a str,
+    ops_location_name: &'a str,
+    ops_location_lat: f64,
+    ops_location_lon: f64,
+}
+
+/// A row of `deadheads.txt`. A pull-out leads to its block's first trip and a pull-in
+/// follows its last.
+#[derive(Debug, Serialize)]
+struct Deadhead<'a> {
+    deadhead_id: String,
+    service_id: &'a str,
+    block_id: &'a str,
+    to_trip_id: Option<&'a str>,
+    from_trip_id: Option<&'a str>,
+}
+
+/// A row of `deadhead_times.txt`, at a garage or at a stop.
+#[derive(Debug, Serialize)]
+struct DeadheadTime<'a> {
+    deadhead_id: String,
+    arrival_time: &'a str,
+    departure_time: &'a str,
+    ops_location_id: Option<&'a str>,
+    stop_id: Option<&'a str>,
+    location_sequence: u32,
+}
+
+/// Writes the deadheads as `ops_locations.txt`, `deadheads.txt` and
+/// `deadhead_times.txt` of the GTFS Operational Data Standard, to go alongside the
+/// feed in `dir`. The stop end of each deadhead is timed at its trip's start or end.
+pub fn write_ods(garages: &[Garage], deadheads: &[BlockDeadheads], rows: &[BlockRow], dir: &str) -> Result<(), Box<dyn Error>> {
+    let trip_times: HashMap<&str, (&str, &str)> =
+        rows.iter().map(|r| (r.trip_id.as_str(), (r.start_time.as_str(), r.end_time.as_str()))).collect();
+
+    let locations: Vec<OpsLocation> = garages
+        .iter()
+        .map(|g| OpsLocation {
+            ops_location_id: &g.garage_id,
+            ops_location_name: &g.garage_name,
+            ops_location_lat: g.garage_lat,
+            ops_location_lon: g.garage_lon,
+        })
+        .collect();
+
+    let (mut trips, mut times) = (Vec::new(), Vec::new());
+    for d in deadheads {
+        let id = |kind: &str| format!("{}_{}_{}", d.service_id, d.block_id, kind);
+        let first_start = trip_times.get(d.first_trip_id.as_str()).map_or("", |t| t.0);
+        let last_end = trip_times.get(d.last_trip_id.as_str()).map_or("", |t| t.1);
+
+        trips.push(Deadhead {
+            deadhead_id: id("pull_out"),
+            service_id: &d.service_id,
+            block_id: &d.block_id,
+            to_trip_id: Some(&d.first_trip_id),
+            from_trip_id: None,
+        });
+        times.push(DeadheadTime {
+            deadhead_id: id("pull_out"),
+            arrival_time: &d.pull_out_time,
+            departure_time: &d.pull_out_time,
+            ops_location_id: Some(&d.pull_out_garage_id),
+            stop_id: None,
+            location_sequence: 1,
+        });
+        times.push(DeadheadTime {
+            deadhead_id: id("pull_out"),
+            arrival_time: first_start,
+            departure_time: first_start,
+            ops_location_id: None,
+            stop_id: Some(&d.pull_out_stop_id),
+            location_sequence: 2,
+        });
+
+        trips.push(Deadhead {
+            deadhead_id: id("pull_in"),
+            service_id: &d.service_id,
+            block_id: &d.block_id,
+            to_trip_id: None,
+            from_trip_id: Some(&d.last_trip_id),
+        });
+        times.push(DeadheadTime {
+            deadhead_id: id("pull_in"),
+            arrival_time: last_end,
+            departure_time: last_end,
+            ops_location_id: None,
+            stop_id: Some(&d.pull_in_stop_id),
+            location_sequence: 1,
+        });
+        times.push(DeadheadTime {
+            deadhead_id: id("pull_in"),
+            arrival_time: &d.pull_in_time,
+            departure_time: &d.pull_in_time,
+            ops_location_id: Some(&d.pull_in_garage_id),
+            stop_id: None,
+            location_sequence: 2,
+        });
+    }
+
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    write_rows(dir.join("ops_locations.txt"), &locations)?;
+    write_rows(dir.join("deadheads.txt"), &trips)?;
+    write_rows(dir.join("deadhead_times.txt"), &times)?;
+    Ok(())
+}
+
+/// Regenerates the feed in memory and adds pull-outs and pull-ins to its manual blocks.
+pub struct Command {
+    pub garages_path: String,
+    pub output_path: String,
+    pub ods_dir: Option<String>, // Where to write the deadheads as GTFS Operational Data Standard files
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command =
+            Command { garages_path: "input/garages.txt".to_string(), output_path: "deadheads.csv".to_string(), ods_dir: None };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--garages" => command.garages_path = args.value("--garages")?,
+                "--output" => command.output_path = args.value("--output")?,
+                "--ods" => command.ods_dir = Some(args.value("--ods")?),
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let feed = generate::build(&generate::Options::in_memory(chrono::Local::now().date_naive()), diagnostics)?;
+        let garages = load(&self.garages_path, diagnostics)?;
+
+        // Manual block conflicts are the blocks command's to report
+        let (rows, _) = blocks::build(&feed, 0, &mut Diagnostics::new());
+        let deadheads = compute(&garages, &rows, &feed.stops, diagnostics);
+
+        write_rows(&self.output_path, &deadheads)?;
+        if let Some(dir) = &self.ods_dir {
+            write_ods(&garages, &deadheads, &rows, dir)?;
+        }
+
+        for d in &deadheads {
+            println!(
+                "{} block {}: pulls out of {} at {} ({} min to stop {}), pulls in to {} at {} ({} min from stop {}); {}% non-revenue",
+                d.service_id,
+                d.block_id,
+                d.pull_out_garage_id,
+                d.pull_out_time,
+                d.pull_out_minutes,
+                d.pull_out_stop_id,
+                d.pull_in_garage_id,
+                d.pull_in_time,
+                d.pull_in_minutes,
+                d.pull_in_stop_id,
+                d.non_revenue_percent
+            );
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Where a problem comes from, as precisely as we know it.
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// A row of an input or output file. Lines are 1-based and include the header.
+    File { path: String, line: Option<u64> },
+    /// An entry in one of the `RawTrip` vectors in `src/data/` (0-based index).
+    RawTrip { route_id: String, service_id: String, index: usize },
+    /// A generated trip.
+    Trip { trip_id: String },
+}
+
+impl Location {
+    pub fn file(path: &str) -> Location {
+        Location::File { path: path.to_string(), line: None }
+    }
+
+    pub fn line(path: &str, line: u64) -> Location {
+        Location::File { path: path.to_string(), line: Some(line) }
+    }
+
+    pub fn raw_trip(route_id: &str, service_id: &str, index: usize) -> Location {
+        Location::RawTrip { route_id: route_id.to_string(), service_id: service_id.to_string(), index }
+    }
+
+    pub fn trip(trip_id: &str) -> Location {
+        Location::Trip { trip_id: trip_id.to_string() }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::File { path, line: Some(line) } => write!(f, "{}:{}", path, line),
+            Location::File { path, line: None } => write!(f, "{}", path),
+            Location::RawTrip { route_id, service_id, index } => {
+                write!(f, "route {} {} RawTrip #{}", route_id, service_id, index)
+            }
+            Location::Trip { trip_id } => write!(f, "trip {}", trip_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<Location>,
+    pub stop_id: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code, message: message.into(), location: None, stop_id: None }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, code, message: message.into(), location: None, stop_id: None }
+    }
+
+    pub fn at(mut self, location: Location) -> Diagnostic {
+        self.location = Some(location);
+        self
+    }
+
+    pub fn stop(mut self, stop_id: impl Into<String>) -> Diagnostic {
+        self.stop_id = Some(stop_id.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}[{}]: {}", level, self.code, self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, "\n  --> {}", location)?;
+        }
+        if let Some(stop_id) = &self.stop_id {
+            write!(f, "\n  stop_id: {}", stop_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Problems collected over a whole run, reported together at the end.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.items.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.items.iter().filter(|d| d.severity == Severity::Warning).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    /// Prints every diagnostic followed by a one-line summary to stderr.
+    pub fn report(&self) {
+        for diagnostic in &self.items {
+            eprintln!("{}\n", diagnostic);
+        }
+        eprintln!("{} error(s), {} warning(s)", self.error_count(), self.warning_count());
+    }
+}
@@ -0,0 +1,315 @@
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::blocks::{self, BlockRow};
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate;
+use crate::gtfs::RawStop;
+use crate::input::{read_csv_lines, read_optional_csv_lines};
+use crate::output::{round, write_rows};
+use crate::stats::{self, TripStats};
+use crate::time::parse_time;
+
+/// The battery, from `vehicle.txt` (one row).
+#[derive(Debug, Deserialize)]
+pub struct Vehicle {
+    pub battery_kwh: f64,
+    #[serde(default)]
+    pub initial_soc: Option<f64>, // Fraction of the battery charged when the block starts; 1 if not given
+    pub min_soc: f64,             // Reserve the charge must not fall below, as a fraction
+}
+
+/// A row of `energy.txt`: consumption for trips averaging up to `max_speed_mph`.
+/// A row without a speed applies to every faster trip.
+#[derive(Debug, Deserialize)]
+pub struct EnergyRate {
+    #[serde(default)]
+    pub max_speed_mph: Option<f64>,
+    pub kwh_per_mile: f64,
+}
+
+/// A row of `chargers.txt`: a charger at a stop, used during layovers there.
+#[derive(Debug, Deserialize)]
+pub struct Charger {
+    pub stop_id: String,
+    pub power_kw: f64,
+}
+
+pub struct ElectricConfig {
+    pub vehicle: Vehicle,
+    pub rates: Vec<EnergyRate>, // Slowest first
+    pub chargers: Vec<Charger>,
+}
+
+impl ElectricConfig {
+    fn kwh_per_mile(&self, speed_mph: f64) -> f64 {
+        self.rates
+            .iter()
+            .find(|r| r.max_speed_mph.is_none_or(|max| speed_mph <= max))
+            .or(self.rates.last())
+            .map_or(0.0, |r| r.kwh_per_mile)
+    }
+}
+
+/// Loads `vehicle.txt`, `energy.txt` and the optional `chargers.txt` from `dir`
+/// (`input/electric/`), reporting values that cannot be simulated. A vehicle that
+/// cannot be simulated is an error.
+pub fn load(dir: &str, stops: &[RawStop], diagnostics: &mut Diagnostics) -> Result<ElectricConfig, Box<dyn Error>> {
+    let path = |name: &str| format!("{}/{}", dir, name);
+
+    let vehicle_path = path("vehicle.txt");
+    let mut vehicles: Vec<(u64, Vehicle)> = read_csv_lines(&vehicle_path, diagnostics)?;
+    if vehicles.len() != 1 {
+        return Err(format!("{} needs exactly one vehicle", vehicle_path).into());
+    }
+    let (line, vehicle) = vehicles.remove(0);
+    let soc_range = 0.0..=1.0;
+    if vehicle.battery_kwh <= 0.0
+        || !soc_range.contains(&vehicle.min_soc)
+        || vehicle.initial_soc.is_some_and(|soc| !soc_range.contains(&soc))
+    {
+        // The state of charge is a fraction of the battery, so there is nothing to simulate
+        return Err(format!(
+            "{}:{}: battery_kwh must be positive and initial_soc and min_soc between 0 and 1",
+            vehicle_path, line
+        )
+        .into());
+    }
+
+    let energy_path = path("energy.txt");
+    let mut rates: Vec<(u64, EnergyRate)> = read_csv_lines(&energy_path, diagnostics)?;
+    if rates.is_empty() {
+        return Err(format!("{} has no energy rates", energy_path).into());
+    }
+    for (line, rate) in &rates {
+        if rate.kwh_per_mile < 0.0 {
+            diagnostics.push(
+                Diagnostic::error("invalid_energy_rate", "kwh_per_mile must not be negative").at(Location::line(&energy_path, *line)),
+            );
+        }
+    }
+    rates.sort_by(|(_, a), (_, b)| {
+        a.max_speed_mph.unwrap_or(f64::INFINITY).total_cmp(&b.max_speed_mph.unwrap_or(f64::INFINITY))
+    });
+
+    let chargers_path = path("chargers.txt");
+    let chargers: Vec<(u64, Charger)> = read_optional_csv_lines(&chargers_path, diagnostics)?;
+    let stop_ids: HashSet<&str> = stops.iter().map(|s| s.stop_id.as_str()).collect();
+    for (line, charger) in &chargers {
+        if !stop_ids.contains(charger.stop_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::warning("unknown_stop", "Charger is at a stop that is not in stops.txt; it is never used")
+                    .at(Location::line(&chargers_path, *line))
+                    .stop(&charger.stop_id),
+            );
+        }
+    }
+
+    Ok(ElectricConfig {
+        vehicle,
+        rates: rates.into_iter().map(|(_, r)| r).collect(),
+        chargers: chargers.into_iter().map(|(_, c)| c).collect(),
+    })
+}
+
+/// State of charge over one trip of a block.
+#[derive(Debug, Serialize)]
+pub struct TripCharge {
+    pub service_id: String,
+    pub block_id: String,
+    pub trip_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub miles: f64,
+    pub average_speed_mph: f64,
+    pub energy_kwh: f64,
+    pub charged_kwh: f64, // During the layover before the trip
+    pub soc_start_kwh: f64,
+    pub soc_end_kwh: f64,
+    pub below_reserve: bool,
+}
+
+/// Whether a block can run on one charge plus its layover charging.
+#[derive(Debug, Serialize)]
+pub struct BlockCharge {
+    pub service_id: String,
+    pub block_id: String,
+    pub trips: usize,
+    pub miles: f64,
+    pub energy_kwh: f64,
+    pub charged_kwh: f64,
+    pub min_soc_percent: f64,
+    pub feasible: bool,
+    pub first_short_trip_id: Option<String>, // The first trip ending below the reserve
+    pub charge_stop_id: Option<String>,      // Where the block lays over before it, and since when
+    pub charge_time: Option<String>,
+    pub shortfall_kwh: f64, // Extra charge needed for the lowest point to stay at the reserve
+}
+
+/// Simulates the state of charge along each block (`rows`, grouped by built block,
+/// or by manual block with `manual`). Each trip uses the energy rate for its average
+/// speed over its shape distance; layovers at a charger add its power for the
+/// layover's length, up to a full battery. A block with trips running at once needs
+/// more than one bus, so it is reported as a conflict and not simulated.
+pub fn simulate(
+    config: &ElectricConfig,
+    rows: &[BlockRow],
+    trips: &[TripStats],
+    manual: bool,
+    diagnostics: &mut Diagnostics,
+) -> (Vec<TripCharge>, Vec<BlockCharge>) {
+    let miles: HashMap<&str, f64> = trips.iter().map(|t| (t.trip_id.as_str(), t.revenue_miles)).collect();
+    let chargers: HashMap<&str, f64> = config.chargers.iter().map(|c| (c.stop_id.as_str(), c.power_kw)).collect();
+    let capacity = config.vehicle.battery_kwh;
+    let reserve = capacity * config.vehicle.min_soc;
+
+    let mut blocks: BTreeMap<(&str, &str), Vec<&BlockRow>> = BTreeMap::new();
+    for row in rows {
+        let block_id = if manual { &row.manual_block_id } else { &row.block_id };
+        blocks.entry((&row.service_id, block_id)).or_default().push(row);
+    }
+
+    let (mut trip_charges, mut block_charges) = (Vec::new(), Vec::new());
+    for ((service_id, block_id), mut block) in blocks {
+        block.sort_by_key(|r| parse_time(&r.start_time));
+        let overlap = block.windows(2).find(|pair| parse_time(&pair[1].start_time) < parse_time(&pair[0].end_time));
+        if let Some(pair) = overlap {
+            let (prev, next) = (pair[0], pair[1]);
+            diagnostics.push(
+                Diagnostic::warning(
+                    "block_conflict",
+                    format!(
+                        "Trip in block {} starts at {}, before trip {} ends at {}; the block is not simulated",
+                        block_id, next.start_time, prev.trip_id, prev.end_time
+                    ),
+                )
+                .at(Location::trip(&next.trip_id)),
+            );
+            continue;
+        }
+
+        let mut soc = capacity * config.vehicle.initial_soc.unwrap_or(1.0);
+        let mut lowest = soc;
+        let (mut total_miles, mut total_energy, mut total_charged) = (0.0, 0.0, 0.0);
+        let mut short: Option<(&BlockRow, Option<&BlockRow>)> = None;
+        for (k, row) in block.iter().enumerate() {
+            let start = parse_time(&row.start_time).unwrap_or(0);
+            let end = parse_time(&row.end_time).unwrap_or(start);
+            let previous = k.checked_sub(1).map(|p| block[p]);
+
+            let charged = previous
+                .and_then(|p| {
+                    let power = chargers.get(p.end_stop_id.as_str())?;
+                    let layover = (start - parse_time(&p.end_time)?).max(0) as f64 / 3600.0;
+                    Some((power * layover).min(capacity - soc).max(0.0))
+                })
+                .unwrap_or(0.0);
+            let soc_start = soc + charged;
+
+            let distance = miles.get(row.trip_id.as_str()).copied().unwrap_or(0.0);
+            let hours = (end - start) as f64 / 3600.0;
+            let speed = if hours > 0.0 { distance / hours } else { 0.0 };
+            let energy = distance * config.kwh_per_mile(speed);
+            soc = soc_start - energy;
+            lowest = lowest.min(soc);
+            if soc < reserve && short.is_none() {
+                short = Some((row, previous));
+            }
+
+            total_miles += distance;
+            total_energy += energy;
+            total_charged += charged;
+            trip_charges.push(TripCharge {
+                service_id: service_id.to_string(),
+                block_id: block_id.to_string(),
+                trip_id: row.trip_id.clone(),
+                start_time: row.start_time.clone(),
+                end_time: row.end_time.clone(),
+                miles: round(distance),
+                average_speed_mph: round(speed),
+                energy_kwh: round(energy),
+                charged_kwh: round(charged),
+                soc_start_kwh: round(soc_start),
+                soc_end_kwh: round(soc),
+                below_reserve: soc < reserve,
+            });
+        }
+
+        block_charges.push(BlockCharge {
+            service_id: service_id.to_string(),
+            block_id: block_id.to_string(),
+            trips: block.len(),
+            miles: round(total_miles),
+            energy_kwh: round(total_energy),
+            charged_kwh: round(total_charged),
+            min_soc_percent: round(lowest / capacity * 100.0),
+            feasible: short.is_none(),
+            first_short_trip_id: short.map(|(row, _)| row.trip_id.clone()),
+            charge_stop_id: short.map(|(row, previous)| previous.map_or(&row.start_stop_id, |p| &p.end_stop_id).clone()),
+            charge_time: short.map(|(row, previous)| previous.map_or(&row.start_time, |p| &p.end_time).clone()),
+            shortfall_kwh: round((reserve - lowest).max(0.0)),
+        });
+    }
+    (trip_charges, block_charges)
+}
+
+/// Writes `trips.csv` and `blocks.csv` to `dir`.
+pub fn write(trips: &[TripCharge], blocks: &[BlockCharge], dir: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    write_rows(dir.join("trips.csv"), trips)?;
+    write_rows(dir.join("blocks.csv"), blocks)?;
+    Ok(())
+}
+
+/// Regenerates the feed in memory and simulates battery-electric buses on its blocks.
+pub struct Command {
+    pub config_dir: String,
+    pub built_blocks: bool, // Simulate the blocks the trips chain into instead of the manual ones
+    pub output_dir: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command =
+            Command { config_dir: "input/electric".to_string(), built_blocks: false, output_dir: "electric".to_string() };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--config" => command.config_dir = args.value("--config")?,
+                "--built-blocks" => command.built_blocks = true,
+                "--output" => command.output_dir = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let today = chrono::Local::now().date_naive();
+        let feed = generate::build(&generate::Options::in_memory(today), diagnostics)?;
+        let config = load(&self.config_dir, &feed.stops, diagnostics)?;
+
+        // Manual block conflicts are the blocks command's to report
+        let (rows, _) = blocks::build(&feed, 0, &mut Diagnostics::new());
+        let stats = stats::compute(&feed, today.year(), &mut Diagnostics::new());
+        let (trips, blocks) = simulate(&config, &rows, &stats.trips, !self.built_blocks, diagnostics);
+        write(&trips, &blocks, &self.output_dir)?;
+
+        for b in &blocks {
+            match (&b.first_short_trip_id, &b.charge_stop_id, &b.charge_time) {
+                (Some(trip_id), Some(stop_id), Some(time)) => println!(
+                    "{} block {}: not feasible, below the reserve after trip {}; needs {:.1} kWh more, e.g. at stop {} from {}",
+                    b.service_id, b.block_id, trip_id, b.shortfall_kwh, stop_id, time
+                ),
+                _ => println!("{} block {}: feasible, lowest charge {:.1}%", b.service_id, b.block_id, b.min_soc_percent),
+            }
+        }
+        println!("Wrote {}/", self.output_dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{FareLegRule, FareMedia, FareProduct, Network, RiderCategory, Route, RouteNetwork};
+use crate::input::read_optional_csv;
+use crate::output::write_rows;
+
+/// GTFS Fares v2 configuration, read from `input/fares/` in GTFS format.
+pub struct Fares {
+    pub networks: Vec<Network>,
+    pub route_networks: Vec<RouteNetwork>,
+    pub fare_media: Vec<FareMedia>,
+    pub rider_categories: Vec<RiderCategory>,
+    pub fare_products: Vec<FareProduct>,
+    pub fare_leg_rules: Vec<FareLegRule>,
+}
+
+/// Writes `rows` to `dir/name` unless there are none, adding the name to `written`.
+fn write_if_any<T: Serialize>(dir: &Path, name: &'static str, rows: &[T], written: &mut Vec<&'static str>) -> Result<(), Box<dyn Error>> {
+    if !rows.is_empty() {
+        write_rows(dir.join(name), rows)?;
+        written.push(name);
+    }
+    Ok(())
+}
+
+/// Loads the fare configuration. Returns `None` when there is no `input/fares/` directory.
+pub fn load(dir: &str, diagnostics: &mut Diagnostics) -> Result<Option<Fares>, Box<dyn Error>> {
+    if !Path::new(dir).is_dir() {
+        return Ok(None);
+    }
+    let path = |name: &str| format!("{}/{}", dir, name);
+
+    Ok(Some(Fares {
+        networks: read_optional_csv(&path("networks.txt"), diagnostics)?,
+        route_networks: read_optional_csv(&path("route_networks.txt"), diagnostics)?,
+        fare_media: read_optional_csv(&path("fare_media.txt"), diagnostics)?,
+        rider_categories: read_optional_csv(&path("rider_categories.txt"), diagnostics)?,
+        fare_products: read_optional_csv(&path("fare_products.txt"), diagnostics)?,
+        fare_leg_rules: read_optional_csv(&path("fare_leg_rules.txt"), diagnostics)?,
+    }))
+}
+
+/// Writes the fare files that have rows and returns their names.
+pub fn write(fares: &Fares, dir: &str) -> Result<Vec<&'static str>, Box<dyn Error>> {
+    let dir = Path::new(dir);
+    let mut written = Vec::new();
+    write_if_any(dir, "networks.txt", &fares.networks, &mut written)?;
+    write_if_any(dir, "route_networks.txt", &fares.route_networks, &mut written)?;
+    write_if_any(dir, "fare_media.txt", &fares.fare_media, &mut written)?;
+    write_if_any(dir, "rider_categories.txt", &fares.rider_categories, &mut written)?;
+    write_if_any(dir, "fare_products.txt", &fares.fare_products, &mut written)?;
+    write_if_any(dir, "fare_leg_rules.txt", &fares.fare_leg_rules, &mut written)?;
+    Ok(written)
+}
+
+/// Checks references between the fare files in `dir` and against routes.txt.
+pub fn check(fares: &Fares, routes: &[Route], dir: &str, diagnostics: &mut Diagnostics) {
+    let file = |name: &str| Location::file(&format!("{}/{}", dir, name));
+
+    let network_ids: HashSet<&str> = fares.networks.iter().map(|n| n.network_id.as_str()).collect();
+    let media_ids: HashSet<&str> = fares.fare_media.iter().map(|m| m.fare_media_id.as_str()).collect();
+    let category_ids: HashSet<&str> = fares.rider_categories.iter().map(|c| c.rider_category_id.as_str()).collect();
+    let product_ids: HashSet<&str> = fares.fare_products.iter().map(|p| p.fare_product_id.as_str()).collect();
+    let route_ids: HashSet<&str> = routes.iter().map(|r| r.route_id.as_str()).collect();
+
+    for rn in &fares.route_networks {
+        if !network_ids.contains(rn.network_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_network",
+                    format!("route_networks.txt references network {} which is not in networks.txt", rn.network_id),
+                )
+                .at(file("route_networks.txt")),
+            );
+        }
+        if !route_ids.contains(rn.route_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_route",
+                    format!("route_networks.txt references route {} which is not in routes.txt", rn.route_id),
+                )
+                .at(file("route_networks.txt")),
+            );
+        }
+    }
+
+    let networked: HashSet<&str> = fares.route_networks.iter().map(|rn| rn.route_id.as_str()).collect();
+    for route in routes {
+        if !networked.contains(route.route_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::warning("route_without_fare_network", format!("Route {} is not in any fare network, so it has no fare", route.route_id))
+                    .at(file("route_networks.txt")),
+            );
+        }
+    }
+
+    for product in &fares.fare_products {
+        if let Some(media) = &product.fare_media_id
+            && !media_ids.contains(media.as_str())
+        {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_media",
+                    format!("Fare product {} references fare media {} which is not in fare_media.txt", product.fare_product_id, media),
+                )
+                .at(file("fare_products.txt")),
+            );
+        }
+        if let Some(category) = &product.rider_category_id
+            && !category_ids.contains(category.as_str())
+        {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_rider_category",
+                    format!(
+                        "Fare product {} references rider category {} which is not in rider_categories.txt",
+                        product.fare_product_id, category
+                    ),
+                )
+                .at(file("fare_products.txt")),
+            );
+        }
+        if !product.amount.parse::<f64>().is_ok_and(|a| a >= 0.0) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_invalid_amount",
+                    format!("Fare product {} has invalid amount \"{}\"", product.fare_product_id, product.amount),
+                )
+                .at(file("fare_products.txt")),
+            );
+        }
+        if product.currency.len() != 3 || !product.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_invalid_currency",
+                    format!(
+                        "Fare product {} has invalid currency \"{}\" (expected an ISO 4217 code)",
+                        product.fare_product_id, product.currency
+                    ),
+                )
+                .at(file("fare_products.txt")),
+            );
+        }
+    }
+
+    for rule in &fares.fare_leg_rules {
+        if let Some(network) = &rule.network_id
+            && !network_ids.contains(network.as_str())
+        {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_network",
+                    format!("fare_leg_rules.txt references network {} which is not in networks.txt", network),
+                )
+                .at(file("fare_leg_rules.txt")),
+            );
+        }
+        if !product_ids.contains(rule.fare_product_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    "fare_unknown_product",
+                    format!("fare_leg_rules.txt references fare product {} which is not in fare_products.txt", rule.fare_product_id),
+                )
+                .at(file("fare_leg_rules.txt")),
+            );
+        }
+    }
+
+    let defaults = fares.rider_categories.iter().filter(|c| c.is_default_fare_category == Some(1)).count();
+    if !fares.rider_categories.is_empty() && defaults != 1 {
+        diagnostics.push(
+            Diagnostic::error(
+                "fare_default_rider_category",
+                format!("rider_categories.txt must have exactly one default category, found {}", defaults),
+            )
+            .at(file("rider_categories.txt")),
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::calendar::{self, Holiday, SpecialService, VersionPeriod};
+use crate::cli::Args;
+use crate::data::{self, EventTrip, RouteSchedule};
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::fares::{self, Fares};
+use crate::gtfs::{Agency, Calendar, CalendarDate, RawShape, RawStop, Route, StopOutput, StopTime, Trip};
+use crate::input::{read_csv, read_id_lines, read_optional_csv_lines};
+use crate::interpolate;
+use crate::output::write_rows;
+use crate::overrides::{self, AgencyOverride, RouteOverride};
+use crate::report::{self, FeedSummary};
+use crate::validate;
+
+pub struct Options {
+    pub rolling_months: Option<u32>,
+    pub expiry_warning_days: i64,
+    pub today: NaiveDate,
+}
+
+impl Options {
+    /// Options for a feed the analysis commands regenerate in memory: every date of
+    /// the schedule versions, without expiry warnings.
+    pub fn in_memory(today: NaiveDate) -> Options {
+        Options { rolling_months: None, expiry_warning_days: 0, today }
+    }
+}
+
+/// The feed built from `input/` and `src/data/`, before it is written.
+pub struct Feed {
+    pub agencies: Vec<Agency>,
+    pub routes: Vec<Route>,
+    pub fares: Option<Fares>,
+    pub calendars: Vec<Calendar>,
+    pub calendar_dates: Vec<CalendarDate>,
+    pub stops: Vec<RawStop>,
+    pub shapes: Vec<RawShape>,
+    pub trips: Vec<Trip>,
+    pub stop_times: Vec<StopTime>,
+    pub schedules: Vec<RouteSchedule>, // The RawTrip entries the trips came from
+}
+
+impl Feed {
+    /// Each trip's stop times, in stop_sequence order.
+    pub fn stop_times_by_trip(&self) -> HashMap<&str, Vec<&StopTime>> {
+        let mut by_trip: HashMap<&str, Vec<&StopTime>> = HashMap::new();
+        for st in &self.stop_times {
+            by_trip.entry(&st.trip_id).or_default().push(st);
+        }
+        for times in by_trip.values_mut() {
+            times.sort_by_key(|st| st.stop_sequence);
+        }
+        by_trip
+    }
+}
+
+pub fn build(options: &Options, diagnostics: &mut Diagnostics) -> Result<Feed, Box<dyn Error>> {
+    // 1. Load Input Data
+    let stops: Vec<RawStop> = read_csv("input/stops.txt", diagnostics)?;
+    let shapes: Vec<RawShape> = read_csv("input/shapes.txt", diagnostics)?;
+    let mut agencies: Vec<Agency> = read_csv("input/agency.txt", diagnostics)?;
+    let mut routes: Vec<Route> = read_csv("input/routes.txt", diagnostics)?;
+
+    // Local corrections layered over the upstream agency and route values
+    let agency_overrides: Vec<(u64, AgencyOverride)> = read_optional_csv_lines("input/agency_overrides.txt", diagnostics)?;
+    let route_overrides: Vec<(u64, RouteOverride)> = read_optional_csv_lines("input/route_overrides.txt", diagnostics)?;
+    overrides::apply_agency_overrides(&mut agencies, &agency_overrides, "input/agency_overrides.txt", diagnostics);
+    overrides::apply_route_overrides(&mut routes, &route_overrides, "input/route_overrides.txt", diagnostics);
+
+    // Fares (GTFS Fares v2)
+    let fares = fares::load("input/fares", diagnostics)?;
+    if let Some(fares) = &fares {
+        fares::check(fares, &routes, "input/fares", diagnostics);
+    }
+
+    // Calendar
+    let Some(periods) = calendar::feed_periods(&data::VERSIONS, options.rolling_months, options.today, diagnostics) else {
+        return Err("Could not determine the feed's date range".into());
+    };
+    // Versions outside the feed period (possible with --rolling-window) are left out
+    let versions: Vec<VersionPeriod> = data::VERSIONS
+        .iter()
+        .zip(periods)
+        .filter_map(|(version, period)| period.map(|(start, end)| VersionPeriod { version, start, end }))
+        .collect();
+    if let Some(end) = versions.iter().map(|v| v.end).max() {
+        calendar::check_expiry(end, options.today, options.expiry_warning_days, diagnostics);
+    }
+
+    let mut calendars = Vec::new();
+    for period in &versions {
+        for service in &data::SERVICES {
+            let [monday, tuesday, wednesday, thursday, friday, saturday, sunday] = service.days;
+            calendars.push(Calendar {
+                service_id: period.version.service_id(service.name),
+                monday, tuesday, wednesday, thursday, friday, saturday, sunday,
+                start_date: calendar::format_date(period.start), end_date: calendar::format_date(period.end),
+            });
+        }
+    }
+
+    // Calendar Dates (Holidays)
+    let holidays: Vec<(u64, Holiday)> = read_optional_csv_lines("input/holidays.txt", diagnostics)?;
+    let mut calendar_dates = calendar::holiday_dates(&holidays, &versions, "input/holidays.txt", diagnostics);
+
+    // Calendar Dates (Special Service), including the dates of event trips
+    let event_trips: Vec<(u64, EventTrip)> = read_optional_csv_lines("input/event_trips.txt", diagnostics)?;
+    let event_services = data::event_services(&event_trips);
+    let special_service: Vec<(u64, SpecialService)> = read_optional_csv_lines("input/special_service.txt", diagnostics)?;
+    calendar_dates.extend(calendar::special_service_dates(
+        &special_service,
+        &versions,
+        &event_services,
+        "input/special_service.txt",
+        diagnostics,
+    ));
+    calendar::check_calendar_dates(&calendar_dates, diagnostics);
+
+    // Trips & Stop Times
+    let schedules: Vec<RouteSchedule> = versions
+        .iter()
+        .flat_map(|period| {
+            let mut routes = data::get_routes(period.version, diagnostics);
+            data::add_event_trips(&mut routes, period.version, &event_trips, "input/event_trips.txt", diagnostics);
+            routes
+        })
+        .collect();
+    let (trips, stop_times) = build_trips(&schedules, &stops, diagnostics)?;
+
+    // Validate the whole feed so problems are reported alongside the output
+    validate::validate(&trips, &stop_times, &stops, diagnostics);
+
+    Ok(Feed {
+        agencies,
+        routes,
+        fares,
+        calendars,
+        calendar_dates,
+        stops,
+        shapes,
+        trips,
+        stop_times,
+        schedules,
+    })
+}
+
+/// Expands route schedules into trips and stop times along each route's
+/// `input/routes/<route_id>/stop_id_sequence.txt`.
+pub fn build_trips(
+    schedules: &[RouteSchedule],
+    stops: &[RawStop],
+    diagnostics: &mut Diagnostics,
+) -> Result<(Vec<Trip>, Vec<StopTime>), Box<dyn Error>> {
+    // Map stop_id to RawStop for easy lookup
+    let stop_map: HashMap<String, &RawStop> = stops.iter()
+        .map(|s| (s.stop_id.clone(), s))
+        .collect();
+
+    let mut trips: Vec<Trip> = Vec::new();
+    let mut stop_times: Vec<StopTime> = Vec::new();
+
+    for route in schedules {
+        // Construct the sequence of stops for this route using the file input
+        // Since the file might contain multiple loops (172 lines vs 86 stops),
+        // we can use the whole sequence and find the subsequence that matches the timepoints.
+        let sequence_path = format!("input/routes/{}/stop_id_sequence.txt", route.route_id);
+        let stop_sequence_ids = read_id_lines(&sequence_path)?;
+
+        if stop_sequence_ids.is_empty() {
+            diagnostics.push(Diagnostic::error("empty_stop_sequence", "Stop sequence is empty").at(Location::file(&sequence_path)));
+            continue;
+        }
+
+        let mut sequence: Vec<&RawStop> = Vec::new();
+        for (line, id) in &stop_sequence_ids {
+            if let Some(stop) = stop_map.get(id) {
+                sequence.push(stop);
+            } else {
+                diagnostics.push(
+                    Diagnostic::error("unknown_stop", "Stop in sequence file is not in stops.txt; it is left out of every trip")
+                        .at(Location::line(&sequence_path, *line))
+                        .stop(id),
+                );
+            }
+        }
+
+        // Match each pattern's timepoints against the sequence once; trips on a pattern
+        // that does not match are left out
+        let mut pattern_positions: HashMap<(usize, usize), Option<Vec<usize>>> = HashMap::new();
+        for trip_input in &route.trips {
+            let pattern = trip_input.pattern;
+            pattern_positions.entry((pattern.first, pattern.last)).or_insert_with(|| {
+                let timepoints = &route.timepoints[pattern.first..=pattern.last];
+                match interpolate::match_timepoints(timepoints, &sequence) {
+                    Ok(positions) => Some(positions),
+                    Err(diagnostic) => {
+                        let message = format!(
+                            "Pattern {}-{} of route {}: {}",
+                            pattern.first, pattern.last, route.route_id, diagnostic.message
+                        );
+                        diagnostics.push(Diagnostic { message, ..*diagnostic }.at(Location::file(&sequence_path)));
+                        None
+                    }
+                }
+            });
+        }
+
+        for trip_input in &route.trips {
+            let pattern = trip_input.pattern;
+            let Some(key_indices) = &pattern_positions[&(pattern.first, pattern.last)] else {
+                continue;
+            };
+
+            let trip_stop_times = interpolate::trip_stop_times(trip_input, route, &sequence, key_indices, diagnostics);
+            if trip_stop_times.is_empty() {
+                continue;
+            }
+
+            trips.push(Trip {
+                route_id: trip_input.route_id.to_string(),
+                service_id: trip_input.service_id.clone(),
+                trip_id: trip_input.trip_id.clone(),
+                shape_id: route.shape_id.to_string(),
+                block_id: trip_input.block_id.clone(),
+                wheelchair_accessible: trip_input.wheelchair_accessible,
+                bikes_allowed: trip_input.bikes_allowed,
+            });
+
+            stop_times.extend(trip_stop_times);
+        }
+    }
+
+    Ok((trips, stop_times))
+}
+
+/// Writes the feed's files to `dir` and returns their names. Shapes are copied from
+/// `input/shapes.txt`.
+pub fn write(feed: &Feed, dir: &str) -> Result<Vec<&'static str>, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let path = Path::new(dir);
+    let mut written = vec!["agency.txt", "calendar.txt", "calendar_dates.txt", "routes.txt"];
+
+    write_rows(path.join("agency.txt"), &feed.agencies)?;
+    write_rows(path.join("calendar.txt"), &feed.calendars)?;
+    write_rows(path.join("calendar_dates.txt"), &feed.calendar_dates)?;
+    write_rows(path.join("routes.txt"), &feed.routes)?;
+    if let Some(fares) = &feed.fares {
+        written.extend(fares::write(fares, dir)?);
+    }
+
+    // Shapes - Just copy provided shapes
+    fs::copy("input/shapes.txt", path.join("shapes.txt"))?;
+
+    // Stops - Just copy provided stops
+    let stops: Vec<StopOutput> = feed
+        .stops
+        .iter()
+        .map(|stop| StopOutput {
+            stop_id: stop.stop_id.clone(),
+            stop_code: "".to_string(),
+            stop_name: stop.stop_name.clone(),
+            stop_lat: stop.stop_lat,
+            stop_lon: stop.stop_lon,
+            wheelchair_boarding: stop.wheelchair_boarding,
+        })
+        .collect();
+    write_rows(path.join("stops.txt"), &stops)?;
+
+    write_rows(path.join("trips.txt"), &feed.trips)?;
+    write_rows(path.join("stop_times.txt"), &feed.stop_times)?;
+    written.extend(["shapes.txt", "stops.txt", "trips.txt", "stop_times.txt"]);
+    Ok(written)
+}
+
+/// The default command: generates the feed into `gtfs/`.
+pub struct Command {
+    pub report_path: Option<String>,
+    pub options: Options,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command {
+            report_path: None,
+            options: Options { rolling_months: None, expiry_warning_days: 30, today: chrono::Local::now().date_naive() },
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--report" => command.report_path = Some(args.value("--report")?),
+                "--rolling-window" => command.options.rolling_months = Some(args.parse("--rolling-window", "number of months")?),
+                "--expiry-warning-days" => command.options.expiry_warning_days = args.parse("--expiry-warning-days", "number of days")?,
+                "--today" => command.options.today = args.date("--today")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    /// Input that cannot be read at all stops the run; everything else is collected
+    /// for the report.
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let mut summary = FeedSummary::default();
+        let mut written = Vec::new();
+        let result = build(&self.options, diagnostics).and_then(|feed| {
+            summary = FeedSummary::of(&feed);
+            written = write(&feed, "gtfs")?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            diagnostics.push(Diagnostic::error("fatal", e.to_string()));
+        }
+
+        if let Some(path) = &self.report_path {
+            let result = report::build(&summary, diagnostics, "gtfs", &written).and_then(|r| report::write(&r, path));
+            if let Err(e) = result {
+                diagnostics.push(Diagnostic::error("report_not_written", e.to_string()).at(Location::file(path)));
+            }
+        }
+        Ok(())
+    }
+}
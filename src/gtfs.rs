@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Agency {
+    pub agency_id: String,
+    pub agency_name: String,
+    pub agency_url: String,
+    pub agency_timezone: String,
+    #[serde(default)]
+    pub agency_lang: Option<String>,
+    #[serde(default)]
+    pub agency_phone: Option<String>,
+    #[serde(default)]
+    pub agency_fare_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Route {
+    pub route_id: String,
+    pub agency_id: String,
+    pub route_short_name: String,
+    pub route_long_name: String,
+    pub route_type: u32,
+    pub route_color: String,
+    pub route_text_color: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Trip {
+    pub route_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    pub shape_id: String,
+    pub block_id: String,
+    pub wheelchair_accessible: u8, // 0 = No info, 1 = Accessible, 2 = Not accessible
+    pub bikes_allowed: u8,         // 0 = No info, 1 = Allowed, 2 = Not allowed
+}
+
+#[derive(Debug, Serialize)]
+pub struct StopTime {
+    pub trip_id: String,
+    pub arrival_time: String,
+    pub departure_time: String,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+    pub stop_headsign: String,
+    pub timepoint: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StopOutput {
+    pub stop_id: String,
+    pub stop_code: String,
+    pub stop_name: String,
+    pub stop_lat: f64,
+    pub stop_lon: f64,
+    pub wheelchair_boarding: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Calendar {
+    pub service_id: String,
+    pub monday: u8,
+    pub tuesday: u8,
+    pub wednesday: u8,
+    pub thursday: u8,
+    pub friday: u8,
+    pub saturday: u8,
+    pub sunday: u8,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarDate {
+    pub service_id: String,
+    pub date: String,
+    pub exception_type: u8, // 1 = Added, 2 = Removed
+}
+
+#[derive(Debug, Serialize)]
+pub struct Transfer {
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub from_route_id: Option<String>,
+    pub to_route_id: Option<String>,
+    pub transfer_type: u8, // 2 = Minimum time given in min_transfer_time
+    pub min_transfer_time: Option<u32>, // Seconds
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawShape {
+    pub shape_id: String,
+    pub shape_pt_lat: f64,
+    pub shape_pt_lon: f64,
+    pub shape_pt_sequence: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawStop {
+    pub stop_id: String,
+    pub stop_name: String,
+    pub stop_lat: f64,
+    pub stop_lon: f64,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    pub wheelchair_boarding: Option<u8>, // 0 = No info, 1 = Accessible, 2 = Not accessible
+}
+
+// Fares v2
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Network {
+    pub network_id: String,
+    pub network_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RouteNetwork {
+    pub network_id: String,
+    pub route_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FareMedia {
+    pub fare_media_id: String,
+    pub fare_media_name: Option<String>,
+    pub fare_media_type: u8, // 0 = None, 1 = Paper ticket, 2 = Transit card, 3 = cEMV, 4 = Mobile app
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RiderCategory {
+    pub rider_category_id: String,
+    pub rider_category_name: String,
+    pub is_default_fare_category: Option<u8>,
+    pub eligibility_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FareProduct {
+    pub fare_product_id: String,
+    pub fare_product_name: Option<String>,
+    pub rider_category_id: Option<String>,
+    pub fare_media_id: Option<String>,
+    pub amount: String, // Kept as written so "0.00" round-trips
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FareLegRule {
+    pub leg_group_id: Option<String>,
+    pub network_id: Option<String>,
+    pub from_area_id: Option<String>,
+    pub to_area_id: Option<String>,
+    pub fare_product_id: String,
+    pub rule_priority: Option<u32>,
+}
@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::Args;
+use crate::diagnostics::Diagnostics;
+use crate::generate::{self, Feed};
+use crate::output::write_rows;
+use crate::time::{format_time, parse_time};
+
+/// Service span and spacing of departures at one stop.
+#[derive(Debug, Serialize)]
+pub struct SpanRow {
+    pub stop_id: String,
+    pub stop_name: String,
+    pub service_id: String,
+    pub trips: usize,
+    pub first_departure: String,
+    pub last_departure: String,
+    pub max_gap_minutes: Option<i64>, // None with a single departure
+    pub bunched: usize,
+}
+
+/// Departures at one stop in one hour, with the average time to the next one.
+#[derive(Debug, Serialize)]
+pub struct HourRow {
+    pub stop_id: String,
+    pub service_id: String,
+    pub hour: i64,
+    pub departures: usize,
+    pub headway_minutes: Option<f64>,
+}
+
+/// Two consecutive departures closer together than the bunching threshold.
+#[derive(Debug, Serialize)]
+pub struct BunchingRow {
+    pub stop_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    pub block_id: String,
+    pub departure_time: String,
+    pub next_trip_id: String,
+    pub next_block_id: String,
+    pub next_departure_time: String,
+    pub gap_minutes: f64,
+}
+
+pub struct Headways {
+    pub spans: Vec<SpanRow>,
+    pub hourly: Vec<HourRow>,
+    pub bunching: Vec<BunchingRow>,
+}
+
+struct Departure<'a> {
+    time: i64,
+    trip_id: &'a str,
+    block_id: &'a str,
+}
+
+/// Headways at every stop for each service, from the feed's stop times (interpolated
+/// ones included). A trip departs from every stop but its last. Consecutive
+/// departures less than `bunching` seconds apart are bunched, even within a block,
+/// since a block whose trips overlap is itself a mistake worth seeing.
+pub fn compute(feed: &Feed, bunching: i64) -> Headways {
+    let trips: HashMap<&str, (&str, &str)> = feed
+        .trips
+        .iter()
+        .map(|t| (t.trip_id.as_str(), (t.service_id.as_str(), t.block_id.as_str())))
+        .collect();
+    let names: HashMap<&str, &str> = feed.stops.iter().map(|s| (s.stop_id.as_str(), s.stop_name.as_str())).collect();
+
+    let by_trip = feed.stop_times_by_trip();
+
+    // Departures by (stop_id, service_id)
+    let mut departures: BTreeMap<(&str, &str), Vec<Departure>> = BTreeMap::new();
+    for (trip_id, stop_times) in &by_trip {
+        let Some(&(service_id, block_id)) = trips.get(trip_id) else { continue };
+        for st in &stop_times[..stop_times.len() - 1] {
+            let Some(time) = parse_time(&st.departure_time) else { continue };
+            departures.entry((&st.stop_id, service_id)).or_default().push(Departure { time, trip_id, block_id });
+        }
+    }
+
+    let mut headways = Headways { spans: Vec::new(), hourly: Vec::new(), bunching: Vec::new() };
+    for ((stop_id, service_id), mut times) in departures {
+        times.sort_by_key(|d| (d.time, d.trip_id));
+        let gaps: Vec<(&Departure, &Departure)> = times.windows(2).map(|pair| (&pair[0], &pair[1])).collect();
+
+        let bunched: Vec<BunchingRow> = gaps
+            .iter()
+            .filter(|(a, b)| b.time - a.time < bunching)
+            .map(|(a, b)| BunchingRow {
+                stop_id: stop_id.to_string(),
+                service_id: service_id.to_string(),
+                trip_id: a.trip_id.to_string(),
+                block_id: a.block_id.to_string(),
+                departure_time: format_time(a.time),
+                next_trip_id: b.trip_id.to_string(),
+                next_block_id: b.block_id.to_string(),
+                next_departure_time: format_time(b.time),
+                gap_minutes: ((b.time - a.time) as f64 / 6.0).round() / 10.0,
+            })
+            .collect();
+
+        headways.spans.push(SpanRow {
+            stop_id: stop_id.to_string(),
+            stop_name: names.get(stop_id).copied().unwrap_or_default().to_string(),
+            service_id: service_id.to_string(),
+            trips: times.len(),
+            first_departure: format_time(times[0].time),
+            last_departure: format_time(times[times.len() - 1].time),
+            max_gap_minutes: gaps.iter().map(|(a, b)| (b.time - a.time) / 60).max(),
+            bunched: bunched.len(),
+        });
+        headways.bunching.extend(bunched);
+
+        // Each gap counts towards the hour of the departure it starts from
+        let mut hours: BTreeMap<i64, (usize, Vec<i64>)> = BTreeMap::new();
+        for departure in &times {
+            hours.entry(departure.time / 3600).or_default().0 += 1;
+        }
+        for (a, b) in &gaps {
+            hours.entry(a.time / 3600).or_default().1.push(b.time - a.time);
+        }
+        for (hour, (count, gaps)) in hours {
+            headways.hourly.push(HourRow {
+                stop_id: stop_id.to_string(),
+                service_id: service_id.to_string(),
+                hour,
+                departures: count,
+                headway_minutes: (!gaps.is_empty())
+                    .then(|| (gaps.iter().sum::<i64>() as f64 / gaps.len() as f64 / 60.0 * 10.0).round() / 10.0),
+            });
+        }
+    }
+    headways
+}
+
+/// Writes `spans.csv`, `hourly.csv` and `bunching.csv` to `dir`.
+pub fn write(headways: &Headways, dir: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    write_rows(dir.join("spans.csv"), &headways.spans)?;
+    write_rows(dir.join("hourly.csv"), &headways.hourly)?;
+    write_rows(dir.join("bunching.csv"), &headways.bunching)?;
+    Ok(())
+}
+
+/// Regenerates the feed in memory and writes the span and headways at every stop.
+pub struct Command {
+    pub bunching: i64, // Minutes
+    pub output_dir: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command { bunching: 3, output_dir: "headways".to_string() };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--bunching" => command.bunching = args.minutes("--bunching")?,
+                "--output" => command.output_dir = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let feed = generate::build(&generate::Options::in_memory(chrono::Local::now().date_naive()), diagnostics)?;
+        let headways = compute(&feed, self.bunching * 60);
+        write(&headways, &self.output_dir)?;
+
+        let mut bunched: BTreeMap<&str, usize> = BTreeMap::new();
+        for row in &headways.bunching {
+            *bunched.entry(&row.service_id).or_default() += 1;
+        }
+        println!(
+            "{} stops; {} departures less than {} min after another",
+            headways.spans.iter().map(|s| s.stop_id.as_str()).collect::<BTreeSet<_>>().len(),
+            headways.bunching.len(),
+            self.bunching
+        );
+        for (service_id, count) in bunched {
+            println!("{}: {} bunched departures", service_id, count);
+        }
+        println!("Wrote {}/", self.output_dir);
+        Ok(())
+    }
+}
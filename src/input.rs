@@ -0,0 +1,70 @@
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+
+/// Reads every row of a CSV file along with its 1-based line number.
+///
+/// Rows that fail to parse are reported with their line and skipped. A file that
+/// cannot be opened at all is a hard error.
+pub fn read_csv_lines<T: DeserializeOwned>(
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<(u64, T)>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path, e))?;
+    let headers: StringRecord = rdr.headers().map_err(|e| format!("{}: {}", path, e))?.clone();
+
+    let mut rows = Vec::new();
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line());
+                diagnostics.push(Diagnostic::error("invalid_csv_row", e.to_string()).at(Location::line(path, line)));
+                continue;
+            }
+        };
+
+        let line = record.position().map_or(0, |p| p.line());
+        match record.deserialize(Some(&headers)) {
+            Ok(row) => rows.push((line, row)),
+            Err(e) => diagnostics.push(
+                Diagnostic::error("invalid_csv_row", format!("Could not read row: {}", e)).at(Location::line(path, line)),
+            ),
+        }
+    }
+    Ok(rows)
+}
+
+pub fn read_csv<T: DeserializeOwned>(path: &str, diagnostics: &mut Diagnostics) -> Result<Vec<T>, Box<dyn Error>> {
+    Ok(read_csv_lines(path, diagnostics)?.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Like `read_csv_lines`, but a missing file means no rows.
+pub fn read_optional_csv_lines<T: DeserializeOwned>(
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<(u64, T)>, Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Ok(Vec::new());
+    }
+    read_csv_lines(path, diagnostics)
+}
+
+pub fn read_optional_csv<T: DeserializeOwned>(path: &str, diagnostics: &mut Diagnostics) -> Result<Vec<T>, Box<dyn Error>> {
+    Ok(read_optional_csv_lines(path, diagnostics)?.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Reads a one-id-per-line file such as `stop_id_sequence.txt`, skipping blank lines.
+pub fn read_id_lines(path: &str) -> Result<Vec<(u64, String)>, Box<dyn Error>> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i as u64 + 1, l.trim().to_string()))
+        .filter(|(_, l)| !l.is_empty())
+        .collect())
+}
@@ -0,0 +1,181 @@
+use crate::data::{RouteSchedule, Timepoint, TripInput};
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{RawStop, StopTime};
+use crate::time::{format_time, parse_time};
+
+/// Finds the index in `sequence` of each of a pattern's timepoints.
+///
+/// `sequence` may contain several loops of the route, so a stop can appear more than
+/// once (Dock 4). A timepoint with an explicit occurrence uses exactly that visit.
+/// Otherwise it takes the first visit after the previous timepoint, and is rejected
+/// as ambiguous if the stop is visited again before the next timepoint (or before
+/// the end of the sequence, for the last one). The error carries the stop id.
+pub fn match_timepoints(timepoints: &[Timepoint], sequence: &[&RawStop]) -> Result<Vec<usize>, Box<Diagnostic>> {
+    let visits = |stop_id: u32| -> Vec<usize> {
+        let stop_id = stop_id.to_string();
+        sequence.iter().enumerate().filter(|(_, s)| s.stop_id == stop_id).map(|(i, _)| i).collect()
+    };
+
+    let mut positions: Vec<usize> = Vec::new();
+    for (i, tp) in timepoints.iter().enumerate() {
+        let candidates = visits(tp.stop_id);
+        let previous = positions.last().copied();
+
+        let position = match tp.occurrence {
+            Some(n) => *candidates.get(n.wrapping_sub(1)).ok_or_else(|| {
+                Box::new(Diagnostic::error(
+                    "timepoint_occurrence_out_of_range",
+                    format!("Timepoint {} asks for visit {} but the stop appears {} time(s) in the sequence", i, n, candidates.len()),
+                )
+                .stop(tp.stop_id.to_string()))
+            })?,
+            None => *candidates.iter().find(|&&p| previous.is_none_or(|prev| p > prev)).ok_or_else(|| {
+                let after = previous.map_or("at all".to_string(), |p| format!("after line {}", p + 1));
+                Box::new(
+                    Diagnostic::error("timepoint_not_in_sequence", format!("Timepoint {} is not in the stop sequence {}", i, after))
+                        .stop(tp.stop_id.to_string()),
+                )
+            })?,
+        };
+
+        if let Some(prev) = previous
+            && position <= prev
+        {
+            return Err(Box::new(Diagnostic::error(
+                "timepoint_out_of_order",
+                format!("Timepoint {} is at line {} of the sequence, not after the previous timepoint (line {})", i, position + 1, prev + 1),
+            )
+            .stop(tp.stop_id.to_string())));
+        }
+        positions.push(position);
+    }
+
+    // Implicit timepoints must be the only visit between their neighbours
+    for (i, tp) in timepoints.iter().enumerate() {
+        if tp.occurrence.is_some() {
+            continue;
+        }
+        let low = if i == 0 { None } else { Some(positions[i - 1]) };
+        let high = positions.get(i + 1).copied().unwrap_or(sequence.len());
+        let candidates: Vec<usize> = visits(tp.stop_id)
+            .into_iter()
+            .filter(|&p| low.is_none_or(|low| p > low) && p < high)
+            .collect();
+
+        if candidates.len() > 1 {
+            let lines: Vec<String> = candidates.iter().map(|p| (p + 1).to_string()).collect();
+            return Err(Box::new(Diagnostic::error(
+                "ambiguous_timepoint",
+                format!("Timepoint {} could be any of lines {} of the sequence; use Timepoint::nth to choose one", i, lines.join(", ")),
+            )
+            .stop(tp.stop_id.to_string())));
+        }
+    }
+
+    Ok(positions)
+}
+
+/// Expands a trip's timepoints into stop times for every stop in `sequence`,
+/// interpolating linearly between timepoints. `key_indices` are the timepoint
+/// positions from `match_timepoints` for the trip's pattern.
+///
+/// Problems are reported against the trip's RawTrip entry. A trip whose timepoint
+/// times are missing or invalid gets no stop times.
+pub fn trip_stop_times(
+    trip_input: &TripInput,
+    route: &RouteSchedule,
+    sequence: &[&RawStop],
+    key_indices: &[usize],
+    diagnostics: &mut Diagnostics,
+) -> Vec<StopTime> {
+    let mut stop_times = Vec::new();
+    let location = Location::raw_trip(route.route_id, &trip_input.service_id, trip_input.raw_index);
+
+    // Resolve every timepoint time up front so a bad one drops the whole trip
+    let mut tp_times = Vec::new();
+    for (tp_id, time) in &trip_input.stops {
+        match time.as_deref().map(|t| (t, parse_time(t))) {
+            Some((_, Some(seconds))) => tp_times.push(seconds),
+            Some((t, None)) => {
+                diagnostics.push(
+                    Diagnostic::error("invalid_timepoint_time", format!("Timepoint time \"{}\" is not a valid HH:MM:SS time", t))
+                        .at(location)
+                        .stop(tp_id.to_string()),
+                );
+                return Vec::new();
+            }
+            None => {
+                diagnostics.push(
+                    Diagnostic::error("missing_timepoint_time", "Timepoint has no scheduled time")
+                        .at(location)
+                        .stop(tp_id.to_string()),
+                );
+                return Vec::new();
+            }
+        }
+    }
+
+    // Now interpolate.
+    // For segments between timepoints.
+    for i in 0..key_indices.len()-1 {
+        let start_idx = key_indices[i];
+        let end_idx = key_indices[i+1];
+        
+        let start_time = tp_times[i];
+        let end_time = tp_times[i+1];
+        
+        let duration_mins = (end_time - start_time) / 60;
+        let num_segments = (end_idx - start_idx) as i64;
+        
+        for j in 0..=num_segments {
+            let current_idx = start_idx + j as usize;
+            
+            // If we are at the very last point of the whole trip, handle it later or now?
+            // The loop handles start to end-1. The last point of segment is start of next.
+            // We add stop times for start..end (exclusive of end? No, inclusive?)
+            // Standard approach: Add start of segment. Intermediate. 
+            // Don't add end of segment (it will be start of next), UNLESS it's the last segment.
+            
+            if current_idx == end_idx && i < key_indices.len() - 2 {
+                continue; // Skip end of segment, let next segment handle it
+            }
+            
+            // Calculate time
+            let added_mins = if num_segments > 0 {
+                duration_mins * j / num_segments
+            } else {
+                0
+            };
+            let current_time = start_time + added_mins * 60;
+            let time_s = format_time(current_time);
+            
+            let stop_id = sequence[current_idx].stop_id.clone();
+            let is_tp = if current_idx == start_idx || current_idx == end_idx { 1 } else { 0 };
+            
+            // Determine headsign
+            let headsign = if let Some(h) = trip_input.pattern.headsign {
+                h.to_string()
+            } else {
+                let idx_in_loop = current_idx % route.loop_length;
+                route.headsigns
+                    .iter()
+                    .rev()
+                    .find(|(start, _)| *start <= idx_in_loop)
+                    .map(|(_, h)| h.to_string())
+                    .unwrap_or_default()
+            };
+
+            stop_times.push(StopTime {
+                trip_id: trip_input.trip_id.clone(),
+                arrival_time: time_s.clone(),
+                departure_time: time_s,
+                stop_id,
+                stop_sequence: (current_idx + 1) as u32,
+                stop_headsign: headsign,
+                timepoint: is_tp,
+            });
+        }
+    }
+
+    stop_times
+}
@@ -0,0 +1,27 @@
+pub mod access;
+pub mod blocks;
+pub mod calendar;
+pub mod cli;
+pub mod connections;
+pub mod data;
+pub mod deadheads;
+pub mod diagnostics;
+pub mod electric;
+pub mod fares;
+pub mod generate;
+pub mod gtfs;
+pub mod headways;
+pub mod input;
+pub mod interpolate;
+pub mod merge;
+pub mod output;
+pub mod overrides;
+pub mod partner;
+pub mod planner;
+pub mod report;
+pub mod scenario;
+pub mod shifts;
+pub mod stats;
+pub mod time;
+pub mod transfers;
+pub mod validate;
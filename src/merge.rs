@@ -0,0 +1,500 @@
+use csv::{Reader, Writer};
+use geo::{HaversineDistance, Point};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate;
+use crate::gtfs::Transfer;
+use crate::input::read_optional_csv_lines;
+use crate::transfers::{self, PartnerConfig, PartnerLink};
+use crate::validate;
+
+// Merging works on the raw CSV rows so every column of every file is kept, not
+// just the ones the generator knows about. Only id columns are rewritten.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum IdKind {
+    Agency,
+    Route,
+    Stop,
+    Trip,
+    Service,
+    Shape,
+    Block,
+    Zone,
+    Fare,
+    Network,
+    FareProduct,
+    FareMedia,
+    RiderCategory,
+    LegGroup,
+    Area,
+    TimeframeGroup,
+}
+
+impl IdKind {
+    fn name(self) -> &'static str {
+        match self {
+            IdKind::Agency => "agency",
+            IdKind::Route => "route",
+            IdKind::Stop => "stop",
+            IdKind::Trip => "trip",
+            IdKind::Service => "service",
+            IdKind::Shape => "shape",
+            IdKind::Block => "block",
+            IdKind::Zone => "zone",
+            IdKind::Fare => "fare",
+            IdKind::Network => "network",
+            IdKind::FareProduct => "fare product",
+            IdKind::FareMedia => "fare media",
+            IdKind::RiderCategory => "rider category",
+            IdKind::LegGroup => "leg group",
+            IdKind::Area => "area",
+            IdKind::TimeframeGroup => "timeframe group",
+        }
+    }
+}
+
+/// The kind of id a column holds, in any GTFS file.
+fn id_kind(column: &str) -> Option<IdKind> {
+    match column {
+        "agency_id" => Some(IdKind::Agency),
+        "route_id" | "from_route_id" | "to_route_id" => Some(IdKind::Route),
+        "stop_id" | "parent_station" | "from_stop_id" | "to_stop_id" => Some(IdKind::Stop),
+        "trip_id" | "from_trip_id" | "to_trip_id" => Some(IdKind::Trip),
+        "service_id" => Some(IdKind::Service),
+        "shape_id" => Some(IdKind::Shape),
+        "block_id" => Some(IdKind::Block),
+        "zone_id" | "origin_id" | "destination_id" | "contains_id" => Some(IdKind::Zone),
+        "fare_id" => Some(IdKind::Fare),
+        "network_id" => Some(IdKind::Network),
+        "fare_product_id" => Some(IdKind::FareProduct),
+        "fare_media_id" => Some(IdKind::FareMedia),
+        "rider_category_id" => Some(IdKind::RiderCategory),
+        "leg_group_id" | "from_leg_group_id" | "to_leg_group_id" => Some(IdKind::LegGroup),
+        "area_id" | "from_area_id" | "to_area_id" => Some(IdKind::Area),
+        "timeframe_group_id" | "from_timeframe_group_id" | "to_timeframe_group_id" => Some(IdKind::TimeframeGroup),
+        _ => None,
+    }
+}
+
+/// The files and columns that define ids, as opposed to referring to them.
+fn defines(file: &str, column: &str) -> Option<IdKind> {
+    match (file, column) {
+        ("agency.txt", "agency_id") => Some(IdKind::Agency),
+        ("routes.txt", "route_id") => Some(IdKind::Route),
+        ("stops.txt", "stop_id") => Some(IdKind::Stop),
+        ("trips.txt", "trip_id") => Some(IdKind::Trip),
+        ("calendar.txt" | "calendar_dates.txt", "service_id") => Some(IdKind::Service),
+        ("shapes.txt", "shape_id") => Some(IdKind::Shape),
+        // Blocks and zones have no file of their own
+        ("trips.txt", "block_id") => Some(IdKind::Block),
+        ("stops.txt", "zone_id") => Some(IdKind::Zone),
+        ("fare_attributes.txt", "fare_id") => Some(IdKind::Fare),
+        ("networks.txt" | "routes.txt", "network_id") => Some(IdKind::Network),
+        ("fare_products.txt", "fare_product_id") => Some(IdKind::FareProduct),
+        ("fare_media.txt", "fare_media_id") => Some(IdKind::FareMedia),
+        ("rider_categories.txt", "rider_category_id") => Some(IdKind::RiderCategory),
+        ("fare_leg_rules.txt", "leg_group_id") => Some(IdKind::LegGroup),
+        ("areas.txt", "area_id") => Some(IdKind::Area),
+        ("timeframes.txt", "timeframe_group_id") => Some(IdKind::TimeframeGroup),
+        _ => None,
+    }
+}
+
+struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn column(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|h| h == name)
+    }
+}
+
+/// A feed to merge. Ids from a feed with a `prefix` are all prefixed; otherwise
+/// only ids that clash with an earlier feed are, with `<feed_id>:`. `links` are a
+/// partner's stops within walking distance of ours, written as transfers.
+pub struct MergeFeed {
+    pub feed_id: String,
+    pub path: String,
+    pub prefix: Option<String>,
+    pub links: Vec<PartnerLink>,
+}
+
+// Columns of the transfers written for partner links
+const TRANSFER_COLUMNS: [&str; 6] =
+    ["from_stop_id", "to_stop_id", "from_route_id", "to_route_id", "transfer_type", "min_transfer_time"];
+
+fn transfer_value(transfer: &Transfer, column: &str) -> String {
+    match column {
+        "from_stop_id" => transfer.from_stop_id.clone(),
+        "to_stop_id" => transfer.to_stop_id.clone(),
+        "from_route_id" => transfer.from_route_id.clone().unwrap_or_default(),
+        "to_route_id" => transfer.to_route_id.clone().unwrap_or_default(),
+        "transfer_type" => transfer.transfer_type.to_string(),
+        "min_transfer_time" => transfer.min_transfer_time.map_or(String::new(), |t| t.to_string()),
+        _ => String::new(),
+    }
+}
+
+struct LoadedFeed<'a> {
+    feed: &'a MergeFeed,
+    tables: BTreeMap<String, Table>,
+}
+
+fn load_table(path: &Path, diagnostics: &mut Diagnostics) -> Result<Table, Box<dyn Error>> {
+    let display = path.to_string_lossy().into_owned();
+    let mut rdr = Reader::from_path(path).map_err(|e| format!("{}: {}", display, e))?;
+    let headers = rdr
+        .headers()
+        .map_err(|e| format!("{}: {}", display, e))?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    for result in rdr.records() {
+        match result {
+            Ok(record) => rows.push(record.iter().map(str::to_string).collect()),
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line());
+                diagnostics.push(Diagnostic::error("invalid_csv_row", e.to_string()).at(Location::line(&display, line)));
+            }
+        }
+    }
+    Ok(Table { headers, rows })
+}
+
+fn load_feed<'a>(feed: &'a MergeFeed, diagnostics: &mut Diagnostics) -> Result<LoadedFeed<'a>, Box<dyn Error>> {
+    let mut tables = BTreeMap::new();
+    for entry in fs::read_dir(&feed.path).map_err(|e| format!("{}: {}", feed.path, e))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "txt") {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        tables.insert(name, load_table(&path, diagnostics)?);
+    }
+    Ok(LoadedFeed { feed, tables })
+}
+
+/// Ids a feed defines, by kind.
+fn defined_ids(feed: &LoadedFeed) -> HashMap<IdKind, HashSet<String>> {
+    let mut ids: HashMap<IdKind, HashSet<String>> = HashMap::new();
+    for (file, table) in &feed.tables {
+        for (i, column) in table.headers.iter().enumerate() {
+            if let Some(kind) = defines(file, column) {
+                ids.entry(kind).or_default().extend(table.rows.iter().filter_map(|r| r.get(i)).cloned());
+            }
+        }
+    }
+    ids
+}
+
+/// The id a feed's agency-less routes belong to: its only (or first) agency.
+fn default_agency(feed: &LoadedFeed) -> String {
+    feed.tables
+        .get("agency.txt")
+        .and_then(|t| t.column("agency_id").and_then(|i| t.rows.first().map(|r| r[i].clone())))
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| feed.feed.feed_id.clone())
+}
+
+/// Stops of later feeds within `distance` meters of a stop of an earlier feed,
+/// mapped to the earlier stop's (merged) id.
+fn duplicate_stops(
+    feeds: &[LoadedFeed],
+    renames: &[HashMap<(IdKind, String), String>],
+    distance: f64,
+) -> HashMap<String, String> {
+    let mut kept: Vec<(String, Point)> = Vec::new();
+    let mut duplicates = HashMap::new();
+    for (feed, renames) in feeds.iter().zip(renames) {
+        let Some(stops) = feed.tables.get("stops.txt") else { continue };
+        let (Some(id), Some(lat), Some(lon)) = (stops.column("stop_id"), stops.column("stop_lat"), stops.column("stop_lon"))
+        else {
+            continue;
+        };
+        let location_type = stops.column("location_type");
+
+        let mut new_stops = Vec::new();
+        for row in &stops.rows {
+            if location_type.is_some_and(|i| !matches!(row[i].trim(), "" | "0")) {
+                continue;
+            }
+            let (Ok(lat), Ok(lon)) = (row[lat].trim().parse::<f64>(), row[lon].trim().parse::<f64>()) else {
+                continue;
+            };
+            let point = Point::new(lon, lat);
+            let merged_id = renames[&(IdKind::Stop, row[id].clone())].clone();
+            match kept.iter().find(|(_, p)| p.haversine_distance(&point) <= distance) {
+                Some((kept_id, _)) => {
+                    duplicates.insert(merged_id, kept_id.clone());
+                }
+                None => new_stops.push((merged_id, point)),
+            }
+        }
+        kept.extend(new_stops);
+    }
+    duplicates
+}
+
+pub struct MergeSummary {
+    pub files: usize,
+    pub renamed_ids: usize,     // Ids renamed because they clashed with an earlier feed
+    pub duplicate_stops: usize, // Stops merged into an earlier feed's stop
+    pub transfers: usize,       // Written for partner links
+}
+
+/// Combines `feeds` (the first is our generated feed) into one feed in `output_dir`.
+///
+/// With `dedupe_distance`, stops of later feeds within that many meters of a stop
+/// of an earlier feed are dropped and references to them point at the earlier stop.
+/// Each partner's links to our stops are added to transfers.txt under the merged ids.
+pub fn merge(
+    feeds: &[MergeFeed],
+    output_dir: &str,
+    dedupe_distance: Option<f64>,
+    diagnostics: &mut Diagnostics,
+) -> Result<MergeSummary, Box<dyn Error>> {
+    let loaded: Vec<LoadedFeed> = feeds.iter().map(|f| load_feed(f, diagnostics)).collect::<Result<_, _>>()?;
+
+    // Work out every feed's renames before writing anything, so ids that a feed
+    // refers to but doesn't define are renamed the same way as defined ones
+    let mut earlier: HashMap<IdKind, HashSet<String>> = HashMap::new();
+    let mut renames: Vec<HashMap<(IdKind, String), String>> = Vec::new();
+    let mut renamed_ids = 0;
+    for feed in &loaded {
+        let mut map = HashMap::new();
+        let mut conflicts: BTreeMap<IdKind, usize> = BTreeMap::new();
+        let rename = |kind: IdKind, id: &str, conflicts: &mut BTreeMap<IdKind, usize>| match &feed.feed.prefix {
+            Some(prefix) => format!("{}{}", prefix, id),
+            None if earlier.get(&kind).is_some_and(|ids| ids.contains(id)) => {
+                *conflicts.entry(kind).or_default() += 1;
+                format!("{}:{}", feed.feed.feed_id, id)
+            }
+            None => id.to_string(),
+        };
+
+        let default_agency = default_agency(feed);
+        map.insert((IdKind::Agency, String::new()), rename(IdKind::Agency, &default_agency, &mut conflicts));
+        for (file, table) in &feed.tables {
+            for (i, column) in table.headers.iter().enumerate() {
+                let Some(kind) = id_kind(column) else { continue };
+                for row in &table.rows {
+                    let id = &row[i];
+                    if id.is_empty() || map.contains_key(&(kind, id.clone())) {
+                        continue;
+                    }
+                    // Count each conflicting id once, where it's defined
+                    let mut ignored = BTreeMap::new();
+                    let counted = if defines(file, column).is_some() { &mut conflicts } else { &mut ignored };
+                    map.insert((kind, id.clone()), rename(kind, id, counted));
+                }
+            }
+        }
+
+        renamed_ids += conflicts.values().sum::<usize>();
+        for (kind, count) in conflicts {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "merged_id_conflict",
+                    format!(
+                        "{} {} id(s) in {} are also used by an earlier feed; renamed to {}:<id>",
+                        count, kind.name(), feed.feed.feed_id, feed.feed.feed_id
+                    ),
+                )
+                .at(Location::file(&feed.feed.path)),
+            );
+        }
+        for (kind, ids) in defined_ids(feed) {
+            let renamed = ids.iter().map(|id| map[&(kind, id.clone())].clone());
+            earlier.entry(kind).or_default().extend(renamed);
+        }
+        renames.push(map);
+    }
+
+    let duplicates = match dedupe_distance {
+        Some(distance) => duplicate_stops(&loaded, &renames, distance),
+        None => HashMap::new(),
+    };
+
+    // Our feed is first, so its ids are never renamed
+    let mut transfers = Vec::new();
+    for (feed, map) in loaded.iter().zip(&renames).skip(1) {
+        let merged = |kind: IdKind, id: &str| map.get(&(kind, id.to_string())).cloned().unwrap_or_else(|| id.to_string());
+        for link in &feed.feed.links {
+            let stop_id = merged(IdKind::Stop, &link.partner_stop_id);
+            let stop_id = duplicates.get(&stop_id).cloned().unwrap_or(stop_id);
+            let route_id = link.partner_route_id.as_deref().map(|id| merged(IdKind::Route, id));
+            // A partner stop merged into ours needs no transfer to it
+            if stop_id != link.stop_id {
+                transfers.extend(link.transfers(&stop_id, route_id.as_deref()));
+            }
+        }
+    }
+    let merged_ids = |kind: IdKind| -> HashSet<&str> {
+        earlier.get(&kind).map_or(HashSet::new(), |ids| {
+            ids.iter().map(String::as_str).filter(|id| !duplicates.contains_key(*id)).collect()
+        })
+    };
+    let transfers_path = Path::new(output_dir).join("transfers.txt").to_string_lossy().into_owned();
+    validate::check_transfer_references(
+        &transfers,
+        &merged_ids(IdKind::Stop),
+        &merged_ids(IdKind::Route),
+        &transfers_path,
+        diagnostics,
+    );
+
+    // Agencies in one feed must share a timezone
+    let timezones: HashSet<&str> = loaded
+        .iter()
+        .filter_map(|f| f.tables.get("agency.txt").map(|t| (t, t.column("agency_timezone"))))
+        .flat_map(|(t, i)| t.rows.iter().filter_map(move |r| i.map(|i| r[i].as_str())))
+        .collect();
+    if timezones.len() > 1 {
+        let mut timezones: Vec<&str> = timezones.into_iter().collect();
+        timezones.sort();
+        diagnostics.push(Diagnostic::warning(
+            "merged_timezone_mismatch",
+            format!("Merged agencies use different timezones: {}", timezones.join(", ")),
+        ));
+    }
+
+    fs::create_dir_all(output_dir)?;
+    let mut files: BTreeMap<&str, ()> = loaded.iter().flat_map(|f| f.tables.keys().map(|k| (k.as_str(), ()))).collect();
+    if !transfers.is_empty() {
+        files.insert("transfers.txt", ());
+    }
+    for file in files.keys() {
+        let mut headers: Vec<&str> = Vec::new();
+        for feed in &loaded {
+            for h in feed.tables.get(*file).map_or(&[][..], |t| &t.headers[..]) {
+                if !headers.contains(&h.as_str()) {
+                    headers.push(h);
+                }
+            }
+        }
+        if *file == "transfers.txt" && !transfers.is_empty() {
+            for column in TRANSFER_COLUMNS {
+                if !headers.contains(&column) {
+                    headers.push(column);
+                }
+            }
+        }
+
+        let path = Path::new(output_dir).join(file);
+        let mut w = Writer::from_path(&path)?;
+        w.write_record(&headers)?;
+        for (feed, map) in loaded.iter().zip(&renames) {
+            let Some(table) = feed.tables.get(*file) else { continue };
+            let stop_id = table.column("stop_id");
+            // A feed may only have one feed_info.txt row; ours wins
+            if *file == "feed_info.txt" && !std::ptr::eq(feed, &loaded[0]) {
+                continue;
+            }
+            let transfer_stops = (*file == "transfers.txt").then(|| (table.column("from_stop_id"), table.column("to_stop_id")));
+            for row in &table.rows {
+                // Duplicate stops are left out; references to them were redirected
+                if *file == "stops.txt" && stop_id.is_some_and(|i| duplicates.contains_key(&map[&(IdKind::Stop, row[i].clone())])) {
+                    continue;
+                }
+                // So are transfers between two stops that became one
+                if let Some((Some(from), Some(to))) = transfer_stops {
+                    let merged = |id: &String| {
+                        let renamed = &map[&(IdKind::Stop, id.clone())];
+                        duplicates.get(renamed).unwrap_or(renamed).clone()
+                    };
+                    let both = !row[from].is_empty() && !row[to].is_empty();
+                    if both && row[from] != row[to] && merged(&row[from]) == merged(&row[to]) {
+                        continue;
+                    }
+                }
+                let mut out = Vec::with_capacity(headers.len());
+                for header in &headers {
+                    let value = table.column(header).map_or("", |i| row[i].as_str());
+                    let Some(kind) = id_kind(header) else {
+                        out.push(value.to_string());
+                        continue;
+                    };
+                    let fill_agency = kind == IdKind::Agency && matches!(*file, "agency.txt" | "routes.txt");
+                    if value.is_empty() && !fill_agency {
+                        out.push(String::new());
+                        continue;
+                    }
+                    let renamed = map[&(kind, value.to_string())].clone();
+                    out.push(match kind {
+                        IdKind::Stop => duplicates.get(&renamed).cloned().unwrap_or(renamed),
+                        _ => renamed,
+                    });
+                }
+                w.write_record(&out)?;
+            }
+        }
+        if *file == "transfers.txt" {
+            for transfer in &transfers {
+                w.write_record(headers.iter().map(|h| transfer_value(transfer, h)))?;
+            }
+        }
+        w.flush()?;
+    }
+
+    Ok(MergeSummary { files: files.len(), renamed_ids, duplicate_stops: duplicates.len(), transfers: transfers.len() })
+}
+
+/// Merges the generated feed in `gtfs/` with the feeds in `input/partner_feeds.txt`.
+pub struct Command {
+    pub output_dir: String,
+    pub dedupe_distance: Option<f64>,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command { output_dir: "merged".to_string(), dedupe_distance: None };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--output" => command.output_dir = args.value("--output")?,
+                "--dedupe-stops" => command.dedupe_distance = Some(args.parse("--dedupe-stops", "distance")?),
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let partners: Vec<(u64, PartnerConfig)> = read_optional_csv_lines("input/partner_feeds.txt", diagnostics)?;
+
+        // Links to partner stops come from the feed regenerated in memory; its input
+        // problems were reported when gtfs/ was generated
+        let options = generate::Options::in_memory(chrono::Local::now().date_naive());
+        let feed = generate::build(&options, &mut Diagnostics::new())?;
+        let mut links: BTreeMap<String, Vec<PartnerLink>> =
+            transfers::build(&partners, "input/partner_feeds.txt", &feed.stops, &feed.trips, &feed.stop_times, diagnostics)
+                .into_iter()
+                .map(|p| (p.feed_id, p.links))
+                .collect();
+
+        let mut feeds =
+            vec![MergeFeed { feed_id: "irvine_connect".to_string(), path: "gtfs".to_string(), prefix: None, links: Vec::new() }];
+        feeds.extend(partners.into_iter().map(|(_, p)| MergeFeed {
+            links: links.remove(&p.feed_id).unwrap_or_default(),
+            feed_id: p.feed_id,
+            path: p.path,
+            prefix: p.id_prefix,
+        }));
+
+        let summary = merge(&feeds, &self.output_dir, self.dedupe_distance, diagnostics)?;
+        println!(
+            "Merged {} feeds into {}/: {} files, {} conflicting ids renamed, {} duplicate stops merged, {} transfers to partner stops",
+            feeds.len(), self.output_dir, summary.files, summary.renamed_ids, summary.duplicate_stops, summary.transfers
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use csv::Writer;
+use serde::Serialize;
+use std::error::Error;
+use std::path::Path;
+
+pub const METERS_PER_MILE: f64 = 1609.344;
+
+/// Rounds to two decimals, as the reports give hours, miles and kWh.
+pub fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Writes `rows` to a CSV file with a header row.
+pub fn write_rows<T: Serialize>(path: impl AsRef<Path>, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    let path = path.as_ref();
+    let mut w = Writer::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    for row in rows {
+        w.serialize(row)?;
+    }
+    w.flush()?;
+    Ok(())
+}
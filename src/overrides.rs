@@ -0,0 +1,120 @@
+use serde::Deserialize;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{Agency, Route};
+
+// Override files use the same columns as agency.txt / routes.txt. The id column is
+// required; any other non-empty column replaces the upstream value.
+
+#[derive(Debug, Deserialize)]
+pub struct AgencyOverride {
+    pub agency_id: String,
+    pub agency_name: Option<String>,
+    pub agency_url: Option<String>,
+    pub agency_timezone: Option<String>,
+    pub agency_lang: Option<String>,
+    pub agency_phone: Option<String>,
+    pub agency_fare_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RouteOverride {
+    pub route_id: String,
+    pub agency_id: Option<String>,
+    pub route_short_name: Option<String>,
+    pub route_long_name: Option<String>,
+    pub route_type: Option<u32>,
+    pub route_color: Option<String>,
+    pub route_text_color: Option<String>,
+}
+
+/// Replaces `target` with `value` when an override is set, noting when the
+/// override disagrees with the upstream value so stale corrections can be spotted.
+fn apply<T: PartialEq + Clone + std::fmt::Display>(
+    target: &mut T,
+    value: &Option<T>,
+    what: String,
+    location: &Location,
+    diagnostics: &mut Diagnostics,
+) {
+    if let Some(value) = value {
+        if target != value {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "override_differs_from_upstream",
+                    format!("{}: upstream has \"{}\", override sets \"{}\"", what, target, value),
+                )
+                .at(location.clone()),
+            );
+        }
+        *target = value.clone();
+    }
+}
+
+fn apply_optional(
+    target: &mut Option<String>,
+    value: &Option<String>,
+    what: String,
+    location: &Location,
+    diagnostics: &mut Diagnostics,
+) {
+    if value.is_none() {
+        return;
+    }
+    let mut current = target.clone().unwrap_or_default();
+    apply(&mut current, value, what, location, diagnostics);
+    *target = Some(current);
+}
+
+/// Applies overrides read from `path`; each entry carries its line in that file.
+pub fn apply_agency_overrides(
+    agencies: &mut [Agency],
+    overrides: &[(u64, AgencyOverride)],
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    for (line, o) in overrides {
+        let location = Location::line(path, *line);
+        let Some(agency) = agencies.iter_mut().find(|a| a.agency_id == o.agency_id) else {
+            diagnostics.push(
+                Diagnostic::error("override_unknown_id", format!("Agency override for {} does not match any agency in agency.txt", o.agency_id))
+                    .at(location),
+            );
+            continue;
+        };
+
+        let what = |field: &str| format!("agency {} {}", o.agency_id, field);
+        apply(&mut agency.agency_name, &o.agency_name, what("agency_name"), &location, diagnostics);
+        apply(&mut agency.agency_url, &o.agency_url, what("agency_url"), &location, diagnostics);
+        apply(&mut agency.agency_timezone, &o.agency_timezone, what("agency_timezone"), &location, diagnostics);
+        apply_optional(&mut agency.agency_lang, &o.agency_lang, what("agency_lang"), &location, diagnostics);
+        apply_optional(&mut agency.agency_phone, &o.agency_phone, what("agency_phone"), &location, diagnostics);
+        apply_optional(&mut agency.agency_fare_url, &o.agency_fare_url, what("agency_fare_url"), &location, diagnostics);
+    }
+}
+
+pub fn apply_route_overrides(
+    routes: &mut [Route],
+    overrides: &[(u64, RouteOverride)],
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    for (line, o) in overrides {
+        let location = Location::line(path, *line);
+        let Some(route) = routes.iter_mut().find(|r| r.route_id == o.route_id) else {
+            diagnostics.push(
+                Diagnostic::error("override_unknown_id", format!("Route override for {} does not match any route in routes.txt", o.route_id))
+                    .at(location),
+            );
+            continue;
+        };
+
+        let what = |field: &str| format!("route {} {}", o.route_id, field);
+        apply(&mut route.agency_id, &o.agency_id, what("agency_id"), &location, diagnostics);
+        apply(&mut route.route_short_name, &o.route_short_name, what("route_short_name"), &location, diagnostics);
+        apply(&mut route.route_long_name, &o.route_long_name, what("route_long_name"), &location, diagnostics);
+        apply(&mut route.route_type, &o.route_type, what("route_type"), &location, diagnostics);
+        apply(&mut route.route_color, &o.route_color, what("route_color"), &location, diagnostics);
+        apply(&mut route.route_text_color, &o.route_text_color, what("route_text_color"), &location, diagnostics);
+    }
+}
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+
+use crate::diagnostics::Diagnostics;
+use crate::gtfs::{Calendar, CalendarDate};
+use crate::input::{read_csv, read_optional_csv};
+
+// A GTFS feed read from disk: another agency's, or our own generated feed for
+// analysis. Only the columns the generator uses are read; everything else in the
+// files is ignored.
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartnerStop {
+    pub stop_id: String,
+    #[serde(default)]
+    pub stop_name: Option<String>,
+    #[serde(default)]
+    pub stop_lat: Option<f64>,
+    #[serde(default)]
+    pub stop_lon: Option<f64>,
+    #[serde(default)]
+    pub location_type: Option<u8>, // Empty or 0 = stop/platform
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartnerTrip {
+    pub route_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    #[serde(default)]
+    pub trip_headsign: Option<String>,
+    #[serde(default)]
+    pub direction_id: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartnerStopTime {
+    pub trip_id: String,
+    #[serde(default)]
+    pub arrival_time: Option<String>,
+    #[serde(default)]
+    pub departure_time: Option<String>,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+}
+
+pub struct PartnerFeed {
+    pub stops: Vec<PartnerStop>,
+    pub trips: Vec<PartnerTrip>,
+    pub stop_times: Vec<PartnerStopTime>,
+    pub calendars: Vec<Calendar>,
+    pub calendar_dates: Vec<CalendarDate>,
+}
+
+pub fn load(dir: &str, diagnostics: &mut Diagnostics) -> Result<PartnerFeed, Box<dyn Error>> {
+    let file = |name: &str| Path::new(dir).join(name).to_string_lossy().into_owned();
+    Ok(PartnerFeed {
+        stops: read_csv(&file("stops.txt"), diagnostics)?,
+        trips: read_csv(&file("trips.txt"), diagnostics)?,
+        stop_times: read_csv(&file("stop_times.txt"), diagnostics)?,
+        calendars: read_optional_csv(&file("calendar.txt"), diagnostics)?,
+        calendar_dates: read_optional_csv(&file("calendar_dates.txt"), diagnostics)?,
+    })
+}
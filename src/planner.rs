@@ -0,0 +1,546 @@
+use chrono::{NaiveDate, Timelike};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+
+use crate::calendar::{format_date, services_on};
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate::{self, Feed};
+use crate::gtfs::StopTime;
+use crate::input::read_optional_csv_lines;
+use crate::output::write_rows;
+use crate::partner::{self, PartnerFeed, PartnerStopTime};
+use crate::time::{format_time, parse_time};
+use crate::transfers::PartnerConfig;
+
+/// Feed id of our own trips in itineraries.
+pub const OUR_FEED_ID: &str = "irvine_connect";
+
+struct Stop {
+    stop_id: String,
+    stop_name: String,
+}
+
+struct PlannerTrip {
+    feed_id: String,
+    route_id: String,
+    trip_id: String,
+    run: usize, // Trips a rider can stay aboard from one to the next share a run
+}
+
+/// A vehicle moving between two consecutive stops of a trip.
+struct Connection {
+    from: usize, // Stop indexes
+    to: usize,
+    departure: i64,
+    arrival: i64,
+    trip: usize,
+}
+
+/// How the earliest arrival at a stop was reached.
+#[derive(Clone, Copy)]
+enum Pointer {
+    Ride { enter: usize, exit: usize }, // Connection indexes
+    Walk { from: usize, seconds: i64 },
+}
+
+/// The connections running on one date, between our stops and, optionally, partner
+/// stops within walking distance of them.
+pub struct Planner {
+    stops: Vec<Stop>,
+    index: HashMap<String, usize>,
+    trips: Vec<PlannerTrip>,
+    connections: Vec<Connection>, // By departure
+    footpaths: Vec<Vec<(usize, i64)>>,
+    runs: usize,
+}
+
+/// One leg of an itinerary: a ride on one trip, or a walk between stops.
+#[derive(Debug, Serialize)]
+pub struct Leg {
+    pub itinerary: usize,
+    pub mode: &'static str, // "ride" or "walk"
+    pub feed_id: Option<String>,
+    pub route_id: Option<String>,
+    pub trip_id: Option<String>,
+    pub from_stop_id: String,
+    pub from_stop_name: String,
+    pub departure_time: String,
+    pub to_stop_id: String,
+    pub to_stop_name: String,
+    pub arrival_time: String,
+    pub stays_on_board: bool, // The bus continues as this trip, e.g. through Dock 4 on the loop
+}
+
+pub struct Itinerary {
+    pub departure: i64, // Of the first leg
+    pub arrival: i64,
+    pub transfers: usize, // Changes of vehicle
+    pub legs: Vec<Leg>,
+}
+
+impl Planner {
+    fn stop(&mut self, stop_id: String, stop_name: String) -> usize {
+        if let Some(&i) = self.index.get(&stop_id) {
+            return i;
+        }
+        self.index.insert(stop_id.clone(), self.stops.len());
+        self.stops.push(Stop { stop_id, stop_name });
+        self.footpaths.push(Vec::new());
+        self.stops.len() - 1
+    }
+
+    fn add_trip(&mut self, feed_id: &str, route_id: &str, trip_id: String, run: Option<usize>) -> usize {
+        let run = run.unwrap_or_else(|| {
+            self.runs += 1;
+            self.runs - 1
+        });
+        self.trips.push(PlannerTrip { feed_id: feed_id.to_string(), route_id: route_id.to_string(), trip_id, run });
+        self.trips.len() - 1
+    }
+
+    /// Connections between consecutive timed stops of a trip, as (stop, arrival, departure).
+    fn add_connections(&mut self, trip: usize, times: &[(usize, Option<i64>, Option<i64>)]) {
+        let timed: Vec<(usize, i64, i64)> = times
+            .iter()
+            .filter_map(|&(stop, arrival, departure)| Some((stop, arrival.or(departure)?, departure.or(arrival)?)))
+            .collect();
+        for pair in timed.windows(2) {
+            let ((from, _, departure), (to, arrival, _)) = (pair[0], pair[1]);
+            self.connections.push(Connection { from, to, departure, arrival, trip });
+        }
+    }
+}
+
+struct BlockTrip<'a> {
+    start: i64,
+    end: i64,
+    from: &'a str, // First and last stop_id
+    to: &'a str,
+    trip_id: &'a str,
+}
+
+/// Follows each of our trips with the next trip of its block, so riders can stay
+/// aboard when the bus goes on from where the trip ends: around the loop through
+/// Dock 4. A trip is followed by the first unclaimed trip of its block that starts at
+/// its last stop once it has arrived, so blocks holding two buses' trips still chain.
+fn our_runs(feed: &Feed, by_trip: &HashMap<&str, Vec<&StopTime>>, running: &HashSet<&str>) -> HashMap<String, usize> {
+    let mut blocks: BTreeMap<(&str, &str), Vec<BlockTrip>> = BTreeMap::new();
+    for trip in feed.trips.iter().filter(|t| running.contains(t.trip_id.as_str())) {
+        let Some(times) = by_trip.get(trip.trip_id.as_str()) else { continue };
+        let (first, last) = (times[0], times[times.len() - 1]);
+        let (Some(start), Some(end)) = (parse_time(&first.departure_time), parse_time(&last.arrival_time)) else { continue };
+        blocks.entry((&trip.service_id, &trip.block_id)).or_default().push(BlockTrip {
+            start,
+            end,
+            from: &first.stop_id,
+            to: &last.stop_id,
+            trip_id: &trip.trip_id,
+        });
+    }
+
+    let mut runs: HashMap<String, usize> = HashMap::new();
+    let mut next_run = 0;
+    for ((_, block_id), mut trips) in blocks {
+        trips.sort_by_key(|t| (t.start, t.trip_id));
+        if block_id.is_empty() {
+            for trip in trips {
+                runs.insert(trip.trip_id.to_string(), next_run);
+                next_run += 1;
+            }
+            continue;
+        }
+        let mut claimed = vec![false; trips.len()];
+        let mut by_end: Vec<usize> = (0..trips.len()).collect();
+        by_end.sort_by_key(|&i| (trips[i].end, i));
+        let mut successor: Vec<Option<usize>> = vec![None; trips.len()];
+        for &i in &by_end {
+            let next = (0..trips.len())
+                .find(|&j| !claimed[j] && j != i && trips[j].start >= trips[i].end && trips[j].from == trips[i].to);
+            if let Some(j) = next {
+                claimed[j] = true;
+                successor[i] = Some(j);
+            }
+        }
+        for first in (0..trips.len()).filter(|&i| !claimed[i]) {
+            let mut trip = Some(first);
+            while let Some(i) = trip {
+                runs.insert(trips[i].trip_id.to_string(), next_run);
+                trip = successor[i];
+            }
+            next_run += 1;
+        }
+    }
+    runs
+}
+
+/// Builds the connections of our trips running on `date` and, for each partner, of
+/// its trips running that day, with its ids prefixed by its `id_prefix` as in a
+/// merged feed. A partner stop id that is also an earlier feed's is renamed to
+/// `<feed_id>:<id>`, with a warning, rather than taken for that feed's stop. Partner
+/// stops within walking distance of our stops are linked to them both ways by their
+/// walking time.
+pub fn build(feed: &Feed, partners: &[(&PartnerConfig, PartnerFeed)], date: NaiveDate, diagnostics: &mut Diagnostics) -> Planner {
+    let mut planner = Planner {
+        stops: Vec::new(),
+        index: HashMap::new(),
+        trips: Vec::new(),
+        connections: Vec::new(),
+        footpaths: Vec::new(),
+        runs: 0,
+    };
+    for stop in &feed.stops {
+        planner.stop(stop.stop_id.clone(), stop.stop_name.clone());
+    }
+
+    let services = services_on(&feed.calendars, &feed.calendar_dates, date);
+    let running: HashSet<&str> =
+        feed.trips.iter().filter(|t| services.contains(&t.service_id)).map(|t| t.trip_id.as_str()).collect();
+    let by_trip = feed.stop_times_by_trip();
+    let runs = our_runs(feed, &by_trip, &running);
+    planner.runs = runs.values().max().map_or(0, |r| r + 1);
+
+    for trip in feed.trips.iter().filter(|t| running.contains(t.trip_id.as_str())) {
+        let Some(times) = by_trip.get(trip.trip_id.as_str()) else { continue };
+        let index = planner.add_trip(OUR_FEED_ID, &trip.route_id, trip.trip_id.clone(), runs.get(&trip.trip_id).copied());
+        let times: Vec<(usize, Option<i64>, Option<i64>)> = times
+            .iter()
+            .map(|st| {
+                let stop = planner.stop(st.stop_id.clone(), String::new());
+                (stop, parse_time(&st.arrival_time), parse_time(&st.departure_time))
+            })
+            .collect();
+        planner.add_connections(index, &times);
+    }
+
+    for (config, partner) in partners {
+        let prefix = config.id_prefix.as_deref().unwrap_or("");
+        let mut partner_stops: HashMap<&str, usize> = HashMap::new();
+        let mut conflicts = 0;
+        for stop in &partner.stops {
+            if partner_stops.contains_key(stop.stop_id.as_str()) {
+                continue;
+            }
+            let mut stop_id = format!("{}{}", prefix, stop.stop_id);
+            if planner.index.contains_key(&stop_id) {
+                conflicts += 1;
+                stop_id = format!("{}:{}", config.feed_id, stop.stop_id);
+            }
+            let index = planner.stop(stop_id, stop.stop_name.clone().unwrap_or_default());
+            partner_stops.insert(&stop.stop_id, index);
+        }
+        if conflicts > 0 {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "planner_stop_id_conflict",
+                    format!(
+                        "{} stop id(s) in {} are also used by an earlier feed; renamed to {}:<id>",
+                        conflicts, config.feed_id, config.feed_id
+                    ),
+                )
+                .at(Location::file(&config.path)),
+            );
+        }
+        for stop in &feed.stops {
+            let ours = planner.index[&stop.stop_id];
+            for (theirs, walk) in config.stops_within_walk(stop.stop_lat, stop.stop_lon, &partner.stops) {
+                let theirs = partner_stops[theirs.stop_id.as_str()];
+                planner.footpaths[ours].push((theirs, walk as i64));
+                planner.footpaths[theirs].push((ours, walk as i64));
+            }
+        }
+
+        let services = services_on(&partner.calendars, &partner.calendar_dates, date);
+        let routes: HashMap<&str, &str> = partner
+            .trips
+            .iter()
+            .filter(|t| services.contains(&t.service_id))
+            .map(|t| (t.trip_id.as_str(), t.route_id.as_str()))
+            .collect();
+        let mut by_trip: BTreeMap<&str, Vec<&PartnerStopTime>> = BTreeMap::new();
+        for st in partner.stop_times.iter().filter(|st| routes.contains_key(st.trip_id.as_str())) {
+            by_trip.entry(&st.trip_id).or_default().push(st);
+        }
+        for (trip_id, mut times) in by_trip {
+            times.sort_by_key(|st| st.stop_sequence);
+            let route_id = format!("{}{}", prefix, routes[trip_id]);
+            let index = planner.add_trip(&config.feed_id, &route_id, format!("{}{}", prefix, trip_id), None);
+            let times: Vec<(usize, Option<i64>, Option<i64>)> = times
+                .iter()
+                .filter_map(|st| {
+                    let stop = *partner_stops.get(st.stop_id.as_str())?;
+                    Some((stop, st.arrival_time.as_deref().and_then(parse_time), st.departure_time.as_deref().and_then(parse_time)))
+                })
+                .collect();
+            planner.add_connections(index, &times);
+        }
+    }
+
+    planner.connections.sort_by_key(|c| (c.departure, c.arrival));
+    planner
+}
+
+impl Planner {
+    /// Earliest arrival at every stop leaving `from` at or after `departure` (seconds),
+    /// by connection scan: connections are scanned by departure, boarding a run at the
+    /// first connection whose stop has been reached in time. With a destination `to`,
+    /// the scan stops once no connection can arrive there sooner.
+    fn scan(&self, from: usize, to: Option<usize>, departure: i64) -> (Vec<i64>, Vec<Option<Pointer>>) {
+        let mut earliest = vec![i64::MAX; self.stops.len()];
+        let mut pointer: Vec<Option<Pointer>> = vec![None; self.stops.len()];
+        let mut boarded: Vec<Option<usize>> = vec![None; self.runs];
+
+        let reach = |stop: usize, time: i64, via: Pointer, earliest: &mut [i64], pointer: &mut [Option<Pointer>]| {
+            if time < earliest[stop] && stop != from {
+                earliest[stop] = time;
+                pointer[stop] = Some(via);
+                for &(next, seconds) in &self.footpaths[stop] {
+                    if time + seconds < earliest[next] && next != from {
+                        earliest[next] = time + seconds;
+                        pointer[next] = Some(Pointer::Walk { from: stop, seconds });
+                    }
+                }
+            }
+        };
+        earliest[from] = departure;
+        for &(next, seconds) in &self.footpaths[from] {
+            reach(next, departure + seconds, Pointer::Walk { from, seconds }, &mut earliest, &mut pointer);
+        }
+
+        let start = self.connections.partition_point(|c| c.departure < departure);
+        for (i, c) in self.connections.iter().enumerate().skip(start) {
+            if to.is_some_and(|to| c.departure >= earliest[to]) {
+                break;
+            }
+            let run = self.trips[c.trip].run;
+            if boarded[run].is_none() && earliest[c.from] <= c.departure {
+                boarded[run] = Some(i);
+            }
+            if let Some(enter) = boarded[run] {
+                reach(c.to, c.arrival, Pointer::Ride { enter, exit: i }, &mut earliest, &mut pointer);
+            }
+        }
+        (earliest, pointer)
+    }
+
+    /// The legs of the earliest arrival at `to`, as the stop each leg reaches and how.
+    fn earliest(&self, from: usize, to: usize, departure: i64) -> Option<Vec<(usize, Pointer)>> {
+        let (earliest, pointer) = self.scan(from, Some(to), departure);
+        if earliest[to] == i64::MAX {
+            return None;
+        }
+
+        // Back from the destination to the origin
+        let mut path = Vec::new();
+        let mut stop = to;
+        while stop != from {
+            let via = pointer[stop]?;
+            path.push((stop, via));
+            stop = match via {
+                Pointer::Ride { enter, .. } => self.connections[enter].from,
+                Pointer::Walk { from, .. } => from,
+            };
+            if path.len() > self.stops.len() {
+                return None;
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn legs(&self, path: &[(usize, Pointer)], itinerary: usize, earliest_at: impl Fn(usize) -> i64) -> Vec<Leg> {
+        let stop_leg = |mode, trip: Option<&PlannerTrip>, from: usize, departure: i64, to: usize, arrival: i64, stays_on_board| Leg {
+            itinerary,
+            mode,
+            feed_id: trip.map(|t| t.feed_id.clone()),
+            route_id: trip.map(|t| t.route_id.clone()),
+            trip_id: trip.map(|t| t.trip_id.clone()),
+            from_stop_id: self.stops[from].stop_id.clone(),
+            from_stop_name: self.stops[from].stop_name.clone(),
+            departure_time: format_time(departure),
+            to_stop_id: self.stops[to].stop_id.clone(),
+            to_stop_name: self.stops[to].stop_name.clone(),
+            arrival_time: format_time(arrival),
+            stays_on_board,
+        };
+
+        let mut legs = Vec::new();
+        for &(stop, via) in path {
+            match via {
+                Pointer::Walk { from, seconds } => {
+                    let arrival = earliest_at(stop);
+                    legs.push(stop_leg("walk", None, from, arrival - seconds, stop, arrival, false));
+                }
+                Pointer::Ride { enter, exit } => {
+                    // One leg per trip of the run ridden
+                    let run = self.trips[self.connections[enter].trip].run;
+                    let ridden: Vec<&Connection> = self.connections[enter..=exit]
+                        .iter()
+                        .filter(|c| self.trips[c.trip].run == run)
+                        .collect();
+                    let mut trips: Vec<Vec<&Connection>> = Vec::new();
+                    for c in ridden {
+                        match trips.last_mut() {
+                            Some(trip) if trip[0].trip == c.trip => trip.push(c),
+                            _ => trips.push(vec![c]),
+                        }
+                    }
+                    for (k, trip) in trips.iter().enumerate() {
+                        let (first, last) = (trip[0], trip[trip.len() - 1]);
+                        let info = &self.trips[first.trip];
+                        legs.push(stop_leg("ride", Some(info), first.from, first.departure, last.to, last.arrival, k > 0));
+                    }
+                }
+            }
+        }
+        legs
+    }
+
+    /// Earliest arrival (seconds) at every stop reachable from `from`, leaving at or
+    /// after `departure`.
+    pub fn arrivals(&self, from: &str, departure: i64) -> Result<HashMap<&str, i64>, String> {
+        let origin = self.index.get(from).copied().ok_or(format!("Stop {} is not in the feed", from))?;
+        let (earliest, _) = self.scan(origin, None, departure);
+        Ok(earliest
+            .iter()
+            .enumerate()
+            .filter(|&(stop, &time)| stop != origin && time != i64::MAX)
+            .map(|(stop, &time)| (self.stops[stop].stop_id.as_str(), time))
+            .collect())
+    }
+
+    /// Up to `count` itineraries from `from` to `to`, the first leaving at or after
+    /// `departure` and each later one leaving after the previous one's first ride.
+    pub fn plan(&self, from: &str, to: &str, departure: i64, count: usize) -> Result<Vec<Itinerary>, String> {
+        let find = |stop_id: &str| self.index.get(stop_id).copied().ok_or(format!("Stop {} is not in the feed", stop_id));
+        let (origin, destination) = (find(from)?, find(to)?);
+        if origin == destination {
+            return Err("The origin and destination are the same stop".to_string());
+        }
+
+        let mut itineraries = Vec::new();
+        let mut leave = departure;
+        while itineraries.len() < count {
+            let Some(path) = self.earliest(origin, destination, leave) else { break };
+            // Walk arrivals are recomputed from the path, each after the leg before it
+            let mut arrivals: HashMap<usize, i64> = HashMap::new();
+            let mut time = leave;
+            for &(stop, via) in &path {
+                time = match via {
+                    Pointer::Ride { exit, .. } => self.connections[exit].arrival,
+                    Pointer::Walk { seconds, .. } => time + seconds,
+                };
+                arrivals.insert(stop, time);
+            }
+            // Leaving later for the same arrival replaces the earlier itinerary
+            if itineraries.last().is_some_and(|i: &Itinerary| i.arrival == time) {
+                itineraries.pop();
+            }
+            let legs = self.legs(&path, itineraries.len() + 1, |stop| arrivals[&stop]);
+            let rides = legs.iter().filter(|l| l.mode == "ride" && !l.stays_on_board).count();
+            let first_ride = path.iter().find_map(|(_, via)| match via {
+                Pointer::Ride { enter, .. } => Some(self.connections[*enter].departure),
+                Pointer::Walk { .. } => None,
+            });
+            itineraries.push(Itinerary {
+                departure: legs.first().and_then(|l| parse_time(&l.departure_time)).unwrap_or(leave),
+                arrival: time,
+                transfers: rides.saturating_sub(1),
+                legs,
+            });
+            // Walking only; no later departure does better
+            let Some(first_ride) = first_ride else { break };
+            leave = first_ride + 1;
+        }
+        Ok(itineraries)
+    }
+}
+
+/// Regenerates the feed in memory for the date and finds the earliest arrivals.
+pub struct Command {
+    pub from: String, // stop_ids
+    pub to: String,
+    pub date: NaiveDate,
+    pub time: i64,      // Seconds after midnight
+    pub partners: bool, // Also ride the feeds in input/partner_feeds.txt
+    pub itineraries: usize,
+    pub output_path: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let now = chrono::Local::now();
+        let mut command = Command {
+            from: String::new(),
+            to: String::new(),
+            date: now.date_naive(),
+            time: now.num_seconds_from_midnight() as i64,
+            partners: false,
+            itineraries: 3,
+            output_path: "plan.csv".to_string(),
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--date" => command.date = args.date("--date")?,
+                "--time" => command.time = args.time("--time")?,
+                "--partners" => command.partners = true,
+                "--itineraries" => command.itineraries = args.parse("--itineraries", "number of itineraries")?,
+                "--output" => command.output_path = args.value("--output")?,
+                stop_id if !stop_id.starts_with('-') && command.from.is_empty() => command.from = stop_id.to_string(),
+                stop_id if !stop_id.starts_with('-') && command.to.is_empty() => command.to = stop_id.to_string(),
+                other => return Err(args.unknown(other)),
+            }
+        }
+        if command.to.is_empty() {
+            return Err(args.error("plan needs an origin and a destination stop_id"));
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let feed = generate::build(&generate::Options::in_memory(self.date), diagnostics)?;
+        let configs: Vec<(u64, PartnerConfig)> = if self.partners {
+            read_optional_csv_lines("input/partner_feeds.txt", diagnostics)?
+        } else {
+            Vec::new()
+        };
+        let mut partners = Vec::new();
+        for (_, config) in &configs {
+            partners.push((config, partner::load(&config.path, diagnostics)?));
+        }
+
+        let planner = build(&feed, &partners, self.date, diagnostics);
+        let itineraries = planner.plan(&self.from, &self.to, self.time, self.itineraries)?;
+
+        let legs: Vec<&Leg> = itineraries.iter().flat_map(|i| &i.legs).collect();
+        write_rows(&self.output_path, &legs)?;
+
+        if itineraries.is_empty() {
+            println!("No way to reach stop {} from stop {} after {} on {}", self.to, self.from, format_time(self.time), format_date(self.date));
+        }
+        for (n, itinerary) in itineraries.iter().enumerate() {
+            println!(
+                "Itinerary {}: {} -> {} ({} min, {} transfers)",
+                n + 1,
+                format_time(itinerary.departure),
+                format_time(itinerary.arrival),
+                (itinerary.arrival - itinerary.departure) / 60,
+                itinerary.transfers
+            );
+            for leg in &itinerary.legs {
+                let how = match (&leg.trip_id, leg.stays_on_board) {
+                    (Some(trip_id), true) => format!("stay on as trip {}", trip_id),
+                    (Some(trip_id), false) => format!("ride trip {}", trip_id),
+                    (None, _) => "walk".to_string(),
+                };
+                println!(
+                    "  {} {} from {} ({}) to {} ({}), arriving {}",
+                    leg.departure_time, how, leg.from_stop_name, leg.from_stop_id, leg.to_stop_name, leg.to_stop_id, leg.arrival_time
+                );
+            }
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostics, Severity};
+use crate::generate::Feed;
+
+/// Facts about the generated feed gathered while building it.
+#[derive(Debug, Default)]
+pub struct FeedSummary {
+    pub trips_per_service: BTreeMap<String, usize>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+impl FeedSummary {
+    pub fn of(feed: &Feed) -> FeedSummary {
+        let mut trips_per_service = BTreeMap::new();
+        for trip in &feed.trips {
+            *trips_per_service.entry(trip.service_id.clone()).or_default() += 1;
+        }
+        FeedSummary {
+            trips_per_service,
+            // YYYYMMDD strings sort chronologically
+            start_date: feed.calendars.iter().map(|c| c.start_date.clone()).min(),
+            end_date: feed.calendars.iter().map(|c| c.end_date.clone()).max(),
+        }
+    }
+}
+
+/// Machine-readable summary of a run, written with `--report <path>` for CI.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub status: &'static str, // "success" or "failure"
+    pub errors: usize,
+    pub warnings: usize,
+    pub feed_start_date: Option<String>,
+    pub feed_end_date: Option<String>,
+    pub services: Vec<ServiceReport>,
+    pub files: Vec<FileReport>,
+    pub diagnostics: Vec<DiagnosticReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceReport {
+    pub service_id: String,
+    pub trips: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub name: String,
+    pub rows: usize, // Excluding the header
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiagnosticReport {
+    pub severity: &'static str,
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<String>,
+    pub stop_id: Option<String>,
+}
+
+/// Lists the files this run wrote to the output directory with their row counts and
+/// SHA-256. Other files left there by earlier runs are not the feed's.
+fn file_reports(output_dir: &Path, written: &[&str]) -> Result<Vec<FileReport>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for name in written {
+        let content = fs::read(output_dir.join(name))?;
+        let rows = csv::Reader::from_reader(content.as_slice()).records().count();
+
+        files.push(FileReport {
+            name: name.to_string(),
+            rows,
+            sha256: format!("{:x}", Sha256::digest(&content)),
+        });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+/// Builds the report of a run that wrote `written` (file names) to `output_dir`.
+pub fn build(summary: &FeedSummary, diagnostics: &Diagnostics, output_dir: &str, written: &[&str]) -> Result<Report, Box<dyn Error>> {
+    Ok(Report {
+        status: if diagnostics.has_errors() { "failure" } else { "success" },
+        errors: diagnostics.error_count(),
+        warnings: diagnostics.warning_count(),
+        feed_start_date: summary.start_date.clone(),
+        feed_end_date: summary.end_date.clone(),
+        services: summary
+            .trips_per_service
+            .iter()
+            .map(|(service_id, &trips)| ServiceReport { service_id: service_id.clone(), trips })
+            .collect(),
+        files: file_reports(Path::new(output_dir), written)?,
+        diagnostics: diagnostics
+            .iter()
+            .map(|d| DiagnosticReport {
+                severity: match d.severity {
+                    Severity::Warning => "warning",
+                    Severity::Error => "error",
+                },
+                code: d.code,
+                message: d.message.clone(),
+                location: d.location.as_ref().map(|l| l.to_string()),
+                stop_id: d.stop_id.clone(),
+            })
+            .collect(),
+    })
+}
+
+pub fn write(report: &Report, path: &str) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(report)?;
+    fs::write(path, json + "\n").map_err(|e| format!("{}: {}", path, e))?;
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::error::Error;
+
+use crate::blocks;
+use crate::cli::Args;
+use crate::data::{self, Pattern, RouteSchedule, TripInput};
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate::{self, Feed};
+use crate::input::read_csv_lines;
+use crate::output::{round, write_rows};
+use crate::stats;
+use crate::time::{format_time, parse_time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Offset,  // The leg ending at `timepoint` takes `minutes` longer (or shorter, if negative)
+    Pattern, // Trips run from timepoint `first` to `last`
+    Headway, // Trips are replaced by one every `minutes`, from timepoint `first` to `last`
+}
+
+/// A row of a scenario file. `service_id` and the `start_time`/`end_time` window
+/// (trips starting at or after `start_time` and before `end_time`) narrow the trips a
+/// pattern or headway change applies to; offset changes apply to the whole route.
+#[derive(Debug, Deserialize)]
+pub struct Change {
+    pub change: ChangeKind,
+    pub route_id: String,
+    #[serde(default)]
+    pub service_id: Option<String>,
+    #[serde(default)]
+    pub timepoint: Option<usize>,
+    #[serde(default)]
+    pub minutes: Option<i64>,
+    #[serde(default)]
+    pub first: Option<usize>,
+    #[serde(default)]
+    pub last: Option<usize>,
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub end_time: Option<String>,
+}
+
+/// Seconds at the trip's first timepoint.
+fn start_of(trip: &TripInput) -> i64 {
+    trip.stops.first().and_then(|(_, time)| time.as_deref()).and_then(parse_time).unwrap_or(0)
+}
+
+/// Checks a change against its route, returning the pattern and time window it uses.
+fn check(change: &Change, route: &RouteSchedule) -> Result<(Option<Pattern>, i64, i64), String> {
+    let window = |time: &Option<String>, default: i64| match time {
+        Some(time) => parse_time(time).ok_or(format!("\"{}\" is not a valid HH:MM time", time)),
+        None => Ok(default),
+    };
+    let (start, end) = (window(&change.start_time, 0)?, window(&change.end_time, i64::MAX)?);
+    let count = route.timepoints.len();
+
+    let pattern = match change.change {
+        ChangeKind::Offset => {
+            if change.timepoint.is_none_or(|t| t == 0 || t >= count) || change.minutes.is_none() {
+                return Err(format!("An offset change needs minutes and a timepoint from 1 to {}", count - 1));
+            }
+            // Later timepoints move with it, so only the one before can end up after it
+            let (timepoint, minutes) = (change.timepoint.unwrap_or(0), change.minutes.unwrap_or(0));
+            let (before, moved) = (route.offsets[timepoint - 1], route.offsets[timepoint] + minutes);
+            if moved < before {
+                return Err(format!(
+                    "Timepoint {} would be {} min from the first, before timepoint {} at {} min; offsets must not decrease",
+                    timepoint,
+                    moved,
+                    timepoint - 1,
+                    before
+                ));
+            }
+            None
+        }
+        ChangeKind::Pattern | ChangeKind::Headway => {
+            let (Some(first), Some(last)) = (change.first, change.last) else {
+                return Err("A pattern or headway change needs first and last timepoints".to_string());
+            };
+            if first >= last || last >= count {
+                return Err(format!("Pattern runs from timepoint {} to {}, but the route has timepoints 0-{}", first, last, count - 1));
+            }
+            if change.change == ChangeKind::Headway
+                && (change.minutes.is_none_or(|m| m <= 0) || change.start_time.is_none() || change.end_time.is_none())
+            {
+                return Err("A headway change needs a positive number of minutes, a start_time and an end_time".to_string());
+            }
+            // Keep the headsign of a pattern the route already runs between the same timepoints
+            let headsign = route
+                .trips
+                .iter()
+                .map(|t| t.pattern)
+                .find(|p| p.first == first && p.last == last)
+                .and_then(|p| p.headsign);
+            Some(Pattern { first, last, headsign })
+        }
+    };
+    Ok((pattern, start, end))
+}
+
+/// Applies a scenario's changes, in order, to the routes. A headway change replaces
+/// the trips starting in its window with trips from `start_time` every `minutes`,
+/// without a block. Changes that don't fit their route are reported and skipped.
+pub fn apply(schedules: &mut [RouteSchedule], changes: &[(u64, Change)], path: &str, diagnostics: &mut Diagnostics) {
+    for (line, change) in changes {
+        let Some(route) = schedules.iter_mut().find(|r| r.route_id == change.route_id) else {
+            diagnostics.push(
+                Diagnostic::error("invalid_scenario", format!("Route {} has no schedule in src/data", change.route_id))
+                    .at(Location::line(path, *line)),
+            );
+            continue;
+        };
+        let (pattern, start, end) = match check(change, route) {
+            Ok(checked) => checked,
+            Err(message) => {
+                diagnostics.push(Diagnostic::error("invalid_scenario", message).at(Location::line(path, *line)));
+                continue;
+            }
+        };
+        let applies = |trip: &TripInput| {
+            change.service_id.as_ref().is_none_or(|s| *s == trip.service_id) && (start..end).contains(&start_of(trip))
+        };
+
+        match (change.change, pattern) {
+            (ChangeKind::Offset, _) => {
+                let (timepoint, minutes) = (change.timepoint.unwrap_or(0), change.minutes.unwrap_or(0));
+                for offset in &mut route.offsets[timepoint..] {
+                    *offset += minutes;
+                }
+            }
+            (ChangeKind::Pattern, Some(pattern)) => {
+                for trip in route.trips.iter_mut().filter(|t| applies(t)) {
+                    // The trip still leaves its old first timepoint at the same time
+                    let shift = route.offsets[pattern.first] - route.offsets[trip.pattern.first];
+                    trip.stops = vec![(0, Some(format_time(start_of(trip) + shift * 60)))];
+                    trip.pattern = pattern;
+                }
+            }
+            (ChangeKind::Headway, Some(pattern)) => {
+                let headway = change.minutes.unwrap_or(0) * 60;
+                let services: BTreeSet<String> = route
+                    .trips
+                    .iter()
+                    .filter(|t| change.service_id.as_ref().is_none_or(|s| *s == t.service_id))
+                    .map(|t| t.service_id.clone())
+                    .collect();
+                let template = route.trips.iter().find(|t| applies(t)).map(|t| (t.wheelchair_accessible, t.bikes_allowed));
+                let (wheelchair_accessible, bikes_allowed) = template.unwrap_or((0, 0));
+                route.trips.retain(|t| !applies(t));
+                for service_id in services {
+                    for (i, time) in (start..end).step_by(headway as usize).enumerate() {
+                        route.trips.push(TripInput {
+                            trip_id: format!("{}_{}_scenario{}_{}", route.route_id, service_id.to_lowercase(), line, i + 1),
+                            raw_index: i,
+                            route_id: route.route_id,
+                            service_id: service_id.clone(),
+                            block_id: String::new(),
+                            wheelchair_accessible,
+                            bikes_allowed,
+                            pattern,
+                            stops: vec![(0, Some(format_time(time)))],
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Retime every trip from its start with the final offsets
+    for route in schedules.iter_mut() {
+        for trip in &mut route.trips {
+            trip.stops = data::timepoint_times(start_of(trip), trip.pattern, &route.timepoints, &route.offsets);
+        }
+    }
+}
+
+/// Baseline and scenario figures for one service.
+#[derive(Debug, Serialize)]
+pub struct ScenarioRow {
+    pub service_id: String,
+    pub baseline_trips: usize,
+    pub scenario_trips: usize,
+    pub baseline_vehicles: usize, // Fewest blocks the trips chain into
+    pub scenario_vehicles: usize,
+    pub baseline_layover_violations: usize, // Trips that cannot follow the previous trip of their manual block
+    pub scenario_layover_violations: usize,
+    pub baseline_revenue_hours: f64, // Per service day
+    pub scenario_revenue_hours: f64,
+    pub revenue_hours_delta: f64,
+    pub annual_revenue_hours_delta: f64,
+}
+
+/// Compares the scenario's feed with the baseline's, service by service.
+pub fn compare(baseline: &Feed, scenario: &Feed, min_layover: i64, year: i32) -> Vec<ScenarioRow> {
+    // Block conflicts are counted here, not reported one by one
+    let mut quiet = Diagnostics::new();
+    let (_, baseline_blocks) = blocks::build(baseline, min_layover, &mut quiet);
+    let (_, scenario_blocks) = blocks::build(scenario, min_layover, &mut quiet);
+    let baseline_stats = stats::compute(baseline, year, &mut quiet);
+    let scenario_stats = stats::compute(scenario, year, &mut quiet);
+
+    baseline_stats
+        .services
+        .iter()
+        .map(|before| {
+            let after = scenario_stats.services.iter().find(|s| s.service_id == before.service_id);
+            let blocks_of = |comparisons: &[blocks::Comparison]| {
+                comparisons.iter().find(|c| c.service_id == before.service_id).map_or((0, 0), |c| (c.blocks, c.manual_conflicts))
+            };
+            let (baseline_vehicles, baseline_layover_violations) = blocks_of(&baseline_blocks);
+            let (scenario_vehicles, scenario_layover_violations) = blocks_of(&scenario_blocks);
+            let scenario_revenue_hours = after.map_or(0.0, |s| s.revenue_hours);
+            let annual = after.map_or(0.0, |s| s.annual_revenue_hours);
+            ScenarioRow {
+                service_id: before.service_id.clone(),
+                baseline_trips: before.trips,
+                scenario_trips: after.map_or(0, |s| s.trips),
+                baseline_vehicles,
+                scenario_vehicles,
+                baseline_layover_violations,
+                scenario_layover_violations,
+                baseline_revenue_hours: before.revenue_hours,
+                scenario_revenue_hours,
+                revenue_hours_delta: round(scenario_revenue_hours - before.revenue_hours),
+                annual_revenue_hours_delta: round(annual - before.annual_revenue_hours),
+            }
+        })
+        .collect()
+}
+
+/// Regenerates the feed in memory with and without a scenario's changes and compares
+/// fleet size, layover violations and revenue hours. No feed is written.
+pub struct Command {
+    pub changes_path: String,
+    pub min_layover: i64, // Minutes
+    pub year: i32,
+    pub output_path: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command {
+            changes_path: String::new(),
+            min_layover: 0,
+            year: chrono::Local::now().year(),
+            output_path: "scenario.csv".to_string(),
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--min-layover" => command.min_layover = args.minutes("--min-layover")?,
+                "--year" => command.year = args.parse("--year", "year")?,
+                "--output" => command.output_path = args.value("--output")?,
+                path if !path.starts_with('-') && command.changes_path.is_empty() => command.changes_path = path.to_string(),
+                other => return Err(args.unknown(other)),
+            }
+        }
+        if command.changes_path.is_empty() {
+            return Err(args.error("scenario needs a file of changes"));
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let changes: Vec<(u64, Change)> = read_csv_lines(&self.changes_path, diagnostics)?;
+        let today = NaiveDate::from_ymd_opt(self.year, 1, 1).ok_or(format!("Invalid year: {}", self.year))?;
+        let options = generate::Options::in_memory(today);
+        let baseline = generate::build(&options, diagnostics)?;
+
+        // The baseline's input problems were reported already
+        let mut scenario = generate::build(&options, &mut Diagnostics::new())?;
+        apply(&mut scenario.schedules, &changes, &self.changes_path, diagnostics);
+        (scenario.trips, scenario.stop_times) = generate::build_trips(&scenario.schedules, &scenario.stops, diagnostics)?;
+
+        let rows = compare(&baseline, &scenario, self.min_layover * 60, self.year);
+        write_rows(&self.output_path, &rows)?;
+
+        for r in &rows {
+            println!(
+                "{}: {} -> {} vehicles, {} -> {} layover violations, {:.2} -> {:.2} revenue hours a day ({:+.2}, {:+.2} in {})",
+                r.service_id, r.baseline_vehicles, r.scenario_vehicles, r.baseline_layover_violations, r.scenario_layover_violations,
+                r.baseline_revenue_hours, r.scenario_revenue_hours, r.revenue_hours_delta, r.annual_revenue_hours_delta, self.year
+            );
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
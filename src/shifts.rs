@@ -0,0 +1,379 @@
+use chrono::{Days, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+
+use crate::calendar::{format_date, services_on};
+use crate::cli::Args;
+use crate::connections::{self, Event, Lines};
+use crate::diagnostics::{Diagnostics, Location};
+use crate::generate::{self, Feed};
+use crate::partner::{self, PartnerFeed};
+use crate::time::parse_time;
+use crate::transfers::{self, PartnerConfig};
+
+/// Bounds on the proposed shifts, in minutes.
+pub struct Limits {
+    pub max_shift: i64,   // Each trip moves at most this far earlier or later
+    pub min_layover: i64, // Between consecutive trips of a block, unless already shorter
+    pub window: i64,      // Connections further apart than this are not made
+}
+
+/// A RawTrip whose start time would move.
+pub struct Shift {
+    pub route_id: String,
+    pub service_id: String,
+    pub raw_index: usize,
+    pub start_time: i64, // Seconds after midnight, before the shift
+    pub minutes: i64,
+}
+
+/// The proposal for one block on one service.
+pub struct BlockShifts {
+    pub service_id: String,
+    pub block_id: String,
+    pub date: NaiveDate, // The date the partner's trains were taken from
+    pub trips: usize,
+    pub wait_before: i64, // Seconds
+    pub wait_after: i64,
+    pub shifts: Vec<Shift>, // Only the trips that move
+}
+
+/// A generated trip with its times at the hub.
+struct BlockTrip<'a> {
+    route_id: &'a str,
+    raw_index: usize,
+    start: i64,
+    end: i64,
+    arrivals: Vec<i64>,
+    departures: Vec<i64>,
+}
+
+/// Total wait of a trip's connections when it is moved by `shift` seconds, as in
+/// `connections::analyze`: each bus arrival against the first train of each line
+/// leaving after the walk, and each bus departure against the last train of each
+/// line arriving in time for it. A connection not made within `window` seconds
+/// costs the whole window.
+fn trip_wait(trip: &BlockTrip, shift: i64, departing: &Lines, arriving: &Lines, window: i64) -> i64 {
+    let best = |trains: &[Event], slack: &dyn Fn(&Event) -> i64| {
+        trains.iter().map(slack).filter(|s| (0..=window).contains(s)).min().unwrap_or(window)
+    };
+    let mut wait = 0;
+    for arrival in &trip.arrivals {
+        for trains in departing.values() {
+            wait += best(trains, &|t| t.time - (arrival + shift + t.walk));
+        }
+    }
+    for departure in &trip.departures {
+        for trains in arriving.values() {
+            wait += best(trains, &|t| departure + shift - (t.time + t.walk));
+        }
+    }
+    wait
+}
+
+/// Chooses a shift for every trip of a block, keeping the trips in order with at
+/// least `min_layover` seconds (or the original layover, if shorter) between them.
+/// Among the shifts with the least total wait, the one moving trips least is taken.
+fn best_shifts(
+    trips: &[BlockTrip],
+    departing: &Lines,
+    arriving: &Lines,
+    limits: &Limits,
+) -> (Vec<i64>, i64) {
+    let choices: Vec<i64> = (-limits.max_shift..=limits.max_shift).map(|m| m * 60).collect();
+
+    // cost[i][k]: least (wait, minutes moved) for trips 0..=i with trip i moved by choices[k]
+    let mut cost: Vec<Vec<Option<(i64, i64)>>> = Vec::new();
+    let mut previous: Vec<Vec<usize>> = Vec::new();
+    for (i, trip) in trips.iter().enumerate() {
+        let mut row = Vec::new();
+        let mut from = Vec::new();
+        for &shift in &choices {
+            let own = (trip_wait(trip, shift, departing, arriving, limits.window * 60), shift.abs() / 60);
+            if i == 0 {
+                row.push(Some(own));
+                from.push(0);
+                continue;
+            }
+            let prev = &trips[i - 1];
+            let layover = (limits.min_layover * 60).min(trip.start - prev.end);
+            let best = choices
+                .iter()
+                .enumerate()
+                .filter(|(_, prev_shift)| trip.start + shift - (prev.end + *prev_shift) >= layover)
+                .filter_map(|(j, _)| cost[i - 1][j].map(|c| (c, j)))
+                .min();
+            row.push(best.map(|((wait, moved), _)| (wait + own.0, moved + own.1)));
+            from.push(best.map_or(0, |(_, j)| j));
+        }
+        cost.push(row);
+        previous.push(from);
+    }
+
+    // Moving nothing is always allowed, so the last trip has a finite cost
+    let Some((mut k, (wait, _))) = cost
+        .last()
+        .and_then(|row| row.iter().enumerate().filter_map(|(k, c)| c.map(|c| (k, c))).min_by_key(|(_, c)| *c))
+    else {
+        return (Vec::new(), 0);
+    };
+    let mut shifts = vec![0; trips.len()];
+    for i in (0..trips.len()).rev() {
+        shifts[i] = choices[k];
+        k = previous[i][k];
+    }
+    (shifts, wait)
+}
+
+/// Proposes start time shifts for the feed's trips that shorten transfer waits with
+/// the partner's trains at `stop_id`. Each service is planned for the first date on
+/// or after `from` (within a week) that it runs, against the trains of that date.
+pub fn propose(
+    feed: &Feed,
+    partner: &PartnerFeed,
+    config: &PartnerConfig,
+    stop_id: &str,
+    from: NaiveDate,
+    limits: &Limits,
+) -> Result<Vec<BlockShifts>, String> {
+    let hub = feed
+        .stops
+        .iter()
+        .find(|s| s.stop_id == stop_id)
+        .ok_or(format!("Stop {} is not in the feed", stop_id))?;
+    let station: HashMap<&str, i64> = config
+        .stops_within_walk(hub.stop_lat, hub.stop_lon, &partner.stops)
+        .into_iter()
+        .map(|(s, walk)| (s.stop_id.as_str(), walk as i64))
+        .collect();
+    if station.is_empty() {
+        return Err(format!("No {} stops are within {} m of stop {}", config.feed_id, config.max_walking_distance, stop_id));
+    }
+
+    let stop_times = feed.stop_times_by_trip();
+
+    // Trips by (service_id, block_id), with their RawTrip position
+    let mut blocks: BTreeMap<(&str, &str), Vec<BlockTrip>> = BTreeMap::new();
+    for input in feed.schedules.iter().flat_map(|r| &r.trips) {
+        let Some(times) = stop_times.get(input.trip_id.as_str()) else { continue };
+        let last = times.len() - 1;
+        let time = |i: usize, arrival: bool| {
+            parse_time(if arrival { &times[i].arrival_time } else { &times[i].departure_time }).unwrap_or(0)
+        };
+        let hub_calls: Vec<usize> = times.iter().enumerate().filter(|(_, st)| st.stop_id == stop_id).map(|(i, _)| i).collect();
+        blocks.entry((&input.service_id, input.block_id.as_str())).or_default().push(BlockTrip {
+            route_id: input.route_id,
+            raw_index: input.raw_index,
+            start: time(0, false),
+            end: time(last, true),
+            arrivals: hub_calls.iter().filter(|&&i| i > 0).map(|&i| time(i, true)).collect(),
+            departures: hub_calls.iter().filter(|&&i| i < last).map(|&i| time(i, false)).collect(),
+        });
+    }
+
+    // The first date each service runs, and the trains of that date
+    let mut dates: HashMap<&str, NaiveDate> = HashMap::new();
+    for offset in 0..7 {
+        let date = from.checked_add_days(Days::new(offset)).ok_or("Date out of range")?;
+        for service in services_on(&feed.calendars, &feed.calendar_dates, date) {
+            if let Some(calendar) = feed.calendars.iter().find(|c| c.service_id == service) {
+                dates.entry(&calendar.service_id).or_insert(date);
+            }
+        }
+    }
+    let mut trains: HashMap<NaiveDate, (Lines, Lines)> = HashMap::new();
+    for &date in dates.values() {
+        trains.entry(date).or_insert_with(|| {
+            let services = services_on(&partner.calendars, &partner.calendar_dates, date);
+            let (arrivals, departures) = connections::events(partner, &services, &station);
+            (connections::by_line(partner, departures), connections::by_line(partner, arrivals))
+        });
+    }
+
+    let mut proposals = Vec::new();
+    for ((service_id, block_id), mut trips) in blocks {
+        let Some(date) = dates.get(service_id) else { continue };
+        let (departing, arriving) = &trains[date];
+        trips.sort_by_key(|t| t.start);
+
+        let wait_before: i64 = trips.iter().map(|t| trip_wait(t, 0, departing, arriving, limits.window * 60)).sum();
+        let (shifts, wait_after) = best_shifts(&trips, departing, arriving, limits);
+        proposals.push(BlockShifts {
+            service_id: service_id.to_string(),
+            block_id: block_id.to_string(),
+            date: *date,
+            trips: trips.len(),
+            wait_before,
+            wait_after,
+            shifts: trips
+                .iter()
+                .zip(shifts)
+                .filter(|(_, shift)| *shift != 0)
+                .map(|(trip, shift)| Shift {
+                    route_id: trip.route_id.to_string(),
+                    service_id: service_id.to_string(),
+                    raw_index: trip.raw_index,
+                    start_time: trip.start,
+                    minutes: shift / 60,
+                })
+                .collect(),
+        });
+    }
+    Ok(proposals)
+}
+
+/// Totals for one service.
+pub struct Summary {
+    pub service_id: String,
+    pub date: String,
+    pub trips: usize,
+    pub shifted: usize,
+    pub wait_before: i64, // Seconds
+    pub wait_after: i64,
+}
+
+pub fn summarize(proposals: &[BlockShifts]) -> Vec<Summary> {
+    let mut summaries: Vec<Summary> = Vec::new();
+    for block in proposals {
+        let index = match summaries.iter().position(|s| s.service_id == block.service_id) {
+            Some(index) => index,
+            None => {
+                summaries.push(Summary {
+                    service_id: block.service_id.clone(),
+                    date: format_date(block.date),
+                    trips: 0,
+                    shifted: 0,
+                    wait_before: 0,
+                    wait_after: 0,
+                });
+                summaries.len() - 1
+            }
+        };
+        let summary = &mut summaries[index];
+        summary.trips += block.trips;
+        summary.shifted += block.shifts.len();
+        summary.wait_before += block.wait_before;
+        summary.wait_after += block.wait_after;
+    }
+    summaries
+}
+
+fn format_start_time(seconds: i64) -> String {
+    format!("{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60)
+}
+
+/// The proposal as a diff of the RawTrip start times in `src/data/`, for review.
+pub fn diff(proposals: &[BlockShifts]) -> String {
+    let mut out = String::new();
+    for block in proposals.iter().filter(|b| !b.shifts.is_empty()) {
+        out.push_str(&format!(
+            "@@ {} block {} ({}): wait {} -> {} min @@\n",
+            block.service_id,
+            block.block_id,
+            format_date(block.date),
+            block.wait_before / 60,
+            block.wait_after / 60
+        ));
+        for shift in &block.shifts {
+            let location = Location::raw_trip(&shift.route_id, &shift.service_id, shift.raw_index);
+            out.push_str(&format!("-{}: start_time: \"{}\"\n", location, format_start_time(shift.start_time)));
+            out.push_str(&format!(
+                "+{}: start_time: \"{}\" ({:+} min)\n",
+                location,
+                format_start_time(shift.start_time + shift.minutes * 60),
+                shift.minutes
+            ));
+        }
+    }
+    out
+}
+
+/// Regenerates the feed in memory and proposes start time shifts that shorten waits
+/// for a partner's trains at the station.
+pub struct Command {
+    pub date: NaiveDate,
+    pub partner: Option<String>, // feed_id in input/partner_feeds.txt; the first one if not given
+    pub stop_id: String,
+    pub limits: Limits,
+    pub output_path: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command {
+            date: chrono::Local::now().date_naive(),
+            partner: None,
+            stop_id: connections::IRVINE_STATION_DOCK_4.to_string(),
+            limits: Limits { max_shift: 5, min_layover: 5, window: 30 },
+            output_path: "schedule_shifts.diff".to_string(),
+        };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--date" => command.date = args.date("--date")?,
+                "--partner" => command.partner = Some(args.value("--partner")?),
+                "--stop" => command.stop_id = args.value("--stop")?,
+                "--max-shift" => command.limits.max_shift = args.minutes("--max-shift")?,
+                "--min-layover" => command.limits.min_layover = args.minutes("--min-layover")?,
+                "--window" => command.limits.window = args.minutes("--window")?,
+                "--output" => command.output_path = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let config = transfers::partner_config(self.partner.as_deref(), diagnostics)?;
+        let feed = generate::build(&generate::Options::in_memory(self.date), diagnostics)?;
+        let theirs = partner::load(&config.path, diagnostics)?;
+        let proposals = propose(&feed, &theirs, &config, &self.stop_id, self.date, &self.limits)?;
+        fs::write(&self.output_path, diff(&proposals))?;
+
+        for s in summarize(&proposals) {
+            println!(
+                "{} ({}): {} of {} trips shifted, total wait {} -> {} min",
+                s.service_id, s.date, s.shifted, s.trips, s.wait_before / 60, s.wait_after / 60
+            );
+        }
+        println!("Wrote {}", self.output_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trip(start: &str, end: &str, arrivals: &[&str]) -> BlockTrip<'static> {
+        BlockTrip {
+            route_id: "R",
+            raw_index: 0,
+            start: parse_time(start).unwrap(),
+            end: parse_time(end).unwrap(),
+            arrivals: arrivals.iter().map(|t| parse_time(t).unwrap()).collect(),
+            departures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn later_trips_move_to_keep_the_layover() {
+        // The first trip makes the 09:08 train by arriving eight minutes later, which
+        // pushes the next trip back as far to keep its five minutes' layover
+        let trips = [trip("08:00", "09:00", &["09:00"]), trip("09:05", "10:00", &[])];
+        let train = Event { trip_id: "t", time: parse_time("09:08").unwrap(), walk: 0 };
+        let departing: Lines = [(("91".to_string(), "0".to_string()), vec![train])].into();
+        let limits = Limits { max_shift: 10, min_layover: 5, window: 30 };
+
+        let (shifts, wait) = best_shifts(&trips, &departing, &Lines::new(), &limits);
+        assert_eq!(shifts, [480, 480]);
+        assert_eq!(wait, 0);
+
+        // A longer layover absorbs part of the move
+        let trips = [trip("08:00", "09:00", &["09:00"]), trip("09:15", "10:00", &[])];
+        let limits = Limits { min_layover: 10, ..limits };
+        let (shifts, wait) = best_shifts(&trips, &departing, &Lines::new(), &limits);
+        assert_eq!(shifts, [480, 180]);
+        assert_eq!(wait, 0);
+    }
+}
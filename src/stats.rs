@@ -0,0 +1,336 @@
+use chrono::{Datelike, NaiveDate};
+use geo::{HaversineDistance, Point};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::calendar::services_on;
+use crate::cli::Args;
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::generate::{self, Feed};
+use crate::gtfs::{RawShape, StopTime};
+use crate::output::{METERS_PER_MILE, round, write_rows};
+use crate::time::{format_time, parse_time};
+
+// A stop is placed on its shape at the first point it comes this close to (meters)
+const SHAPE_MATCH_DISTANCE: f64 = 150.0;
+
+#[derive(Debug, Serialize)]
+pub struct TripStats {
+    pub trip_id: String,
+    pub route_id: String,
+    pub service_id: String,
+    pub block_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub revenue_hours: f64,
+    pub revenue_miles: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockStats {
+    pub service_id: String,
+    pub block_id: String,
+    pub trips: usize,
+    pub vehicles: usize, // More than one where the block's trips overlap
+    pub start_time: String,
+    pub end_time: String,
+    pub revenue_hours: f64,
+    pub layover_hours: f64, // Between each vehicle's trips
+    pub revenue_miles: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceStats {
+    pub service_id: String,
+    pub trips: usize,
+    pub blocks: usize,
+    pub peak_vehicles: usize, // Most vehicles in service at once
+    pub revenue_hours: f64,   // Per service day
+    pub layover_hours: f64,
+    pub revenue_miles: f64,
+    pub days: usize, // Days the service runs in the year, after holidays
+    pub annual_revenue_hours: f64,
+    pub annual_layover_hours: f64,
+    pub annual_revenue_miles: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub year: i32,
+    pub services: Vec<ServiceStats>,
+    pub blocks: Vec<BlockStats>,
+    pub trips: Vec<TripStats>,
+}
+
+/// Cumulative distance (meters) at each point of each shape, in sequence order.
+fn shape_distances(shapes: &[RawShape]) -> HashMap<&str, Vec<(Point, f64)>> {
+    let mut points: HashMap<&str, Vec<&RawShape>> = HashMap::new();
+    for point in shapes {
+        points.entry(&point.shape_id).or_default().push(point);
+    }
+    points
+        .into_iter()
+        .map(|(shape_id, mut points)| {
+            points.sort_by_key(|p| p.shape_pt_sequence);
+            let mut distance = 0.0;
+            let mut previous: Option<Point> = None;
+            let measured = points
+                .iter()
+                .map(|p| {
+                    let point = Point::new(p.shape_pt_lon, p.shape_pt_lat);
+                    distance += previous.map_or(0.0, |prev| prev.haversine_distance(&point));
+                    previous = Some(point);
+                    (point, distance)
+                })
+                .collect();
+            (shape_id, measured)
+        })
+        .collect()
+}
+
+/// Distance along the shape at each stop of one loop of a route's stop sequence.
+/// Stops are placed in order, each at the closest point of its first approach after
+/// the previous stop, so roads the loop travels twice are told apart.
+fn place_stops(shape: &[(Point, f64)], stops: &[Option<Point>]) -> Vec<Option<f64>> {
+    let mut index = 0;
+    let mut placed = Vec::new();
+    for stop in stops {
+        let Some(stop) = stop else {
+            placed.push(None);
+            continue;
+        };
+        let distance = |i: usize| shape[i].0.haversine_distance(stop);
+        let mut best = match (index..shape.len()).find(|&i| distance(i) <= SHAPE_MATCH_DISTANCE) {
+            Some(first) => first,
+            // Nowhere close; take the nearest point still ahead
+            None => (index..shape.len()).min_by(|&a, &b| distance(a).total_cmp(&distance(b))).unwrap_or(index),
+        };
+        while best + 1 < shape.len() && distance(best + 1) < distance(best) {
+            best += 1;
+        }
+        placed.push(shape.get(best).map(|p| p.1));
+        index = best;
+    }
+    placed
+}
+
+/// Distance along the shape of every position in each route's stop sequence, as
+/// (distances within one loop, length of the loop's shape). The stops at each
+/// position are taken from the generated stop times, whose stop_sequence is the
+/// position in `stop_id_sequence.txt`.
+fn route_distances<'a>(feed: &'a Feed, stops: &HashMap<&str, Point>) -> HashMap<&'a str, (Vec<Option<f64>>, f64)> {
+    let shapes = shape_distances(&feed.shapes);
+    let route_of: HashMap<&str, &str> = feed.trips.iter().map(|t| (t.trip_id.as_str(), t.route_id.as_str())).collect();
+
+    let mut routes = HashMap::new();
+    for schedule in &feed.schedules {
+        let Some(shape) = shapes.get(schedule.shape_id) else { continue };
+        let loop_length = schedule.loop_length.max(1);
+        let mut loop_stops: Vec<Option<Point>> = vec![None; loop_length];
+        for st in &feed.stop_times {
+            if route_of.get(st.trip_id.as_str()) == Some(&schedule.route_id) {
+                let position = (st.stop_sequence as usize).saturating_sub(1) % loop_length;
+                loop_stops[position] = loop_stops[position].or(stops.get(st.stop_id.as_str()).copied());
+            }
+        }
+        let length = shape.last().map_or(0.0, |p| p.1);
+        routes.insert(schedule.route_id, (place_stops(shape, &loop_stops), length));
+    }
+    routes
+}
+
+/// Revenue hours and miles of the feed's trips, by trip, block and service, with
+/// each service's daily totals multiplied by the days it runs in `year`. A block
+/// whose trips overlap is run by more than one vehicle, and reported as a conflict.
+pub fn compute(feed: &Feed, year: i32, diagnostics: &mut Diagnostics) -> Stats {
+    let stops: HashMap<&str, Point> =
+        feed.stops.iter().map(|s| (s.stop_id.as_str(), Point::new(s.stop_lon, s.stop_lat))).collect();
+    let routes = route_distances(feed, &stops);
+    let stop_times = feed.stop_times_by_trip();
+
+    // Trips, with their times in seconds for the block and service totals
+    let mut trips = Vec::new();
+    let mut spans: Vec<(i64, i64)> = Vec::new();
+    for trip in &feed.trips {
+        let Some(times) = stop_times.get(trip.trip_id.as_str()) else { continue };
+        let start = times.first().and_then(|st| parse_time(&st.departure_time)).unwrap_or(0);
+        let end = times.last().and_then(|st| parse_time(&st.arrival_time)).unwrap_or(start);
+        // Whole loops between the first and last stop count the shape's full length
+        let meters = match (routes.get(trip.route_id.as_str()), times.first(), times.last()) {
+            (Some((placed, length)), Some(first), Some(last)) => {
+                let at = |st: &StopTime| {
+                    let position = st.stop_sequence as usize - 1;
+                    placed[position % placed.len()].map(|d| (position / placed.len()) as f64 * length + d)
+                };
+                at(last).zip(at(first)).map_or(0.0, |(end, start)| end - start)
+            }
+            _ => 0.0,
+        };
+        trips.push(TripStats {
+            trip_id: trip.trip_id.clone(),
+            route_id: trip.route_id.clone(),
+            service_id: trip.service_id.clone(),
+            block_id: trip.block_id.clone(),
+            start_time: format_time(start),
+            end_time: format_time(end),
+            revenue_hours: (end - start) as f64 / 3600.0,
+            revenue_miles: meters / METERS_PER_MILE,
+        });
+        spans.push((start, end));
+    }
+
+    // Blocks, with their trips in time order
+    let mut by_block: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+    for (i, trip) in trips.iter().enumerate() {
+        by_block.entry((&trip.service_id, &trip.block_id)).or_default().push(i);
+    }
+    let mut blocks = Vec::new();
+    let mut vehicle_spans: Vec<Vec<(i64, i64)>> = Vec::new(); // By block
+    for ((service_id, block_id), mut members) in by_block {
+        members.sort_by_key(|&i| spans[i].0);
+        let start = members.iter().map(|&i| spans[i].0).min().unwrap_or(0);
+        let end = members.iter().map(|&i| spans[i].1).max().unwrap_or(0);
+        // Each trip goes to the first vehicle free by its start, as (first start, last end)
+        let mut vehicles: Vec<(i64, i64)> = Vec::new();
+        let mut layover = 0;
+        for &i in &members {
+            let (trip_start, trip_end) = spans[i];
+            match vehicles.iter_mut().find(|v| v.1 <= trip_start) {
+                Some(vehicle) => {
+                    layover += trip_start - vehicle.1;
+                    vehicle.1 = trip_end;
+                }
+                None => {
+                    if let Some(busy_until) = vehicles.iter().map(|v| v.1).min() {
+                        diagnostics.push(
+                            Diagnostic::warning(
+                                "block_conflict",
+                                format!(
+                                    "Trip in block {} starts at {}, before its other trips end at {}; counted as another vehicle",
+                                    block_id,
+                                    format_time(trip_start),
+                                    format_time(busy_until)
+                                ),
+                            )
+                            .at(Location::trip(&trips[i].trip_id)),
+                        );
+                    }
+                    vehicles.push((trip_start, trip_end));
+                }
+            }
+        }
+        blocks.push(BlockStats {
+            service_id: service_id.to_string(),
+            block_id: block_id.to_string(),
+            trips: members.len(),
+            vehicles: vehicles.len(),
+            start_time: format_time(start),
+            end_time: format_time(end),
+            revenue_hours: members.iter().map(|&i| trips[i].revenue_hours).sum(),
+            layover_hours: layover as f64 / 3600.0,
+            revenue_miles: members.iter().map(|&i| trips[i].revenue_miles).sum(),
+        });
+        vehicle_spans.push(vehicles);
+    }
+
+    // Days each service runs in the year
+    let mut days: HashMap<String, usize> = HashMap::new();
+    if let Some(first) = NaiveDate::from_ymd_opt(year, 1, 1) {
+        for date in first.iter_days().take_while(|d| d.year() == year) {
+            for service in services_on(&feed.calendars, &feed.calendar_dates, date) {
+                *days.entry(service).or_default() += 1;
+            }
+        }
+    }
+
+    let mut services: Vec<ServiceStats> = Vec::new();
+    for calendar in &feed.calendars {
+        let members: Vec<usize> = (0..blocks.len()).filter(|&i| blocks[i].service_id == calendar.service_id).collect();
+        // Vehicles in service at each vehicle's start; the most is the peak
+        let vehicles: Vec<(i64, i64)> = members.iter().flat_map(|&i| vehicle_spans[i].iter().copied()).collect();
+        let peak_vehicles = vehicles
+            .iter()
+            .map(|&(at, _)| vehicles.iter().filter(|&&(start, end)| start <= at && at < end).count())
+            .max()
+            .unwrap_or(0);
+        let sum = |f: fn(&BlockStats) -> f64| members.iter().map(|&i| f(&blocks[i])).sum::<f64>();
+        let (revenue_hours, layover_hours, revenue_miles) =
+            (sum(|b| b.revenue_hours), sum(|b| b.layover_hours), sum(|b| b.revenue_miles));
+        let days = days.get(&calendar.service_id).copied().unwrap_or(0);
+        services.push(ServiceStats {
+            service_id: calendar.service_id.clone(),
+            trips: members.iter().map(|&i| blocks[i].trips).sum(),
+            blocks: members.len(),
+            peak_vehicles,
+            revenue_hours: round(revenue_hours),
+            layover_hours: round(layover_hours),
+            revenue_miles: round(revenue_miles),
+            days,
+            annual_revenue_hours: round(revenue_hours * days as f64),
+            annual_layover_hours: round(layover_hours * days as f64),
+            annual_revenue_miles: round(revenue_miles * days as f64),
+        });
+    }
+
+    for block in &mut blocks {
+        block.revenue_hours = round(block.revenue_hours);
+        block.layover_hours = round(block.layover_hours);
+        block.revenue_miles = round(block.revenue_miles);
+    }
+    for trip in &mut trips {
+        trip.revenue_hours = round(trip.revenue_hours);
+        trip.revenue_miles = round(trip.revenue_miles);
+    }
+    Stats { year, services, blocks, trips }
+}
+
+/// Writes `services.csv`, `blocks.csv`, `trips.csv` and all three in `stats.json`.
+pub fn write(stats: &Stats, dir: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let dir = Path::new(dir);
+    write_rows(dir.join("services.csv"), &stats.services)?;
+    write_rows(dir.join("blocks.csv"), &stats.blocks)?;
+    write_rows(dir.join("trips.csv"), &stats.trips)?;
+    fs::write(dir.join("stats.json"), serde_json::to_string_pretty(stats)? + "\n")?;
+    Ok(())
+}
+
+/// Regenerates the feed in memory and writes revenue hours, miles and peak vehicles.
+pub struct Command {
+    pub year: i32,
+    pub output_dir: String,
+}
+
+impl Command {
+    pub fn parse(args: &mut Args) -> Result<Command, String> {
+        let mut command = Command { year: chrono::Local::now().year(), output_dir: "stats".to_string() };
+        while let Some(arg) = args.next_arg()? {
+            match arg.as_str() {
+                "--year" => command.year = args.parse("--year", "year")?,
+                "--output" => command.output_dir = args.value("--output")?,
+                other => return Err(args.unknown(other)),
+            }
+        }
+        Ok(command)
+    }
+
+    pub fn run(&self, diagnostics: &mut Diagnostics) -> Result<(), Box<dyn Error>> {
+        let today = NaiveDate::from_ymd_opt(self.year, 1, 1).ok_or(format!("Invalid year: {}", self.year))?;
+        let feed = generate::build(&generate::Options::in_memory(today), diagnostics)?;
+        let stats = compute(&feed, self.year, diagnostics);
+        write(&stats, &self.output_dir)?;
+
+        for s in &stats.services {
+            println!(
+                "{}: {} trips, {} peak vehicles, {:.2} revenue hours and {:.2} revenue miles a day; {} days in {}: {:.2} hours, {:.2} miles",
+                s.service_id, s.trips, s.peak_vehicles, s.revenue_hours, s.revenue_miles, s.days, self.year,
+                s.annual_revenue_hours, s.annual_revenue_miles
+            );
+        }
+        println!("Wrote {}/", self.output_dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+// GTFS times are measured from noon minus 12h on the service day, so trips that run
+// past midnight use hours of 24 or more ("25:10:00"). chrono's NaiveTime wraps at
+// midnight, so schedule times are kept as seconds instead.
+
+/// Parses "HH:MM" or "HH:MM:SS" into seconds since the start of the service day.
+pub fn parse_time(s: &str) -> Option<i64> {
+    let mut parts = s.trim().split(':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let seconds: i64 = match parts.next() {
+        Some(p) => p.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() || hours < 0 || !(0..60).contains(&minutes) || !(0..60).contains(&seconds) {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Formats seconds since the start of the service day as "HH:MM:SS".
+pub fn format_time(seconds: i64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
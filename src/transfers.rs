@@ -0,0 +1,189 @@
+use geo::{HaversineDistance, Point};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{RawStop, StopTime, Transfer, Trip};
+use crate::input::read_optional_csv_lines;
+use crate::partner::{self, PartnerFeed, PartnerStop};
+
+// Walking speed used when a partner feed doesn't set one (m/s)
+pub const DEFAULT_WALKING_SPEED: f64 = 1.2;
+
+/// A row of `input/partner_feeds.txt`: another agency's GTFS feed on disk to
+/// connect with.
+#[derive(Debug, Deserialize)]
+pub struct PartnerConfig {
+    pub feed_id: String,
+    pub path: String,
+    #[serde(default)]
+    pub id_prefix: Option<String>, // Prepended to the partner's stop and route ids, e.g. "metrolink:"
+    pub max_walking_distance: f64, // Meters
+    #[serde(default)]
+    pub walking_speed: Option<f64>, // m/s
+    #[serde(default)]
+    pub by_route: Option<u8>, // 1 = one transfer per pair of routes, with from_route_id/to_route_id
+}
+
+impl PartnerConfig {
+    /// The partner's stops (not stations) within walking distance of a point, with
+    /// their walking time in seconds.
+    pub fn stops_within_walk<'a>(&self, lat: f64, lon: f64, stops: &'a [PartnerStop]) -> Vec<(&'a PartnerStop, u32)> {
+        let speed = self.walking_speed.unwrap_or(DEFAULT_WALKING_SPEED);
+        let here = Point::new(lon, lat);
+        stops
+            .iter()
+            .filter(|s| s.location_type.is_none_or(|t| t == 0))
+            .filter_map(|s| {
+                let distance = here.haversine_distance(&Point::new(s.stop_lon?, s.stop_lat?));
+                (distance <= self.max_walking_distance).then_some((s, (distance / speed).ceil() as u32))
+            })
+            .collect()
+    }
+}
+
+/// The partner feed `feed_id` in `input/partner_feeds.txt`, or the first one there.
+pub fn partner_config(feed_id: Option<&str>, diagnostics: &mut Diagnostics) -> Result<PartnerConfig, Box<dyn Error>> {
+    let partners: Vec<(u64, PartnerConfig)> = read_optional_csv_lines("input/partner_feeds.txt", diagnostics)?;
+    partners
+        .into_iter()
+        .map(|(_, p)| p)
+        .find(|p| feed_id.is_none_or(|id| p.feed_id == id))
+        .ok_or("No such partner feed in input/partner_feeds.txt".into())
+}
+
+/// Routes serving each stop.
+fn routes_by_stop<'a>(
+    trips: impl Iterator<Item = (&'a str, &'a str)>,
+    stop_times: impl Iterator<Item = (&'a str, &'a str)>,
+) -> HashMap<&'a str, BTreeSet<&'a str>> {
+    let route_of_trip: HashMap<&str, &str> = trips.collect();
+    let mut routes: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for (trip_id, stop_id) in stop_times {
+        if let Some(route_id) = route_of_trip.get(trip_id) {
+            routes.entry(stop_id).or_default().insert(route_id);
+        }
+    }
+    routes
+}
+
+/// One of our stops and a partner stop within walking distance of it. Partner ids
+/// are as the partner feed has them, since merging decides what they become.
+#[derive(Debug, Clone)]
+pub struct PartnerLink {
+    pub stop_id: String,
+    pub route_id: Option<String>,
+    pub partner_stop_id: String,
+    pub partner_route_id: Option<String>,
+    pub walking_time: u32, // Seconds
+}
+
+impl PartnerLink {
+    /// The transfers in both directions, given the partner's stop and route ids in
+    /// the merged feed. `min_transfer_time` is the walking time.
+    pub fn transfers(&self, partner_stop_id: &str, partner_route_id: Option<&str>) -> [Transfer; 2] {
+        let transfer = |from_stop: &str, to_stop: &str, from_route: Option<&str>, to_route: Option<&str>| Transfer {
+            from_stop_id: from_stop.to_string(),
+            to_stop_id: to_stop.to_string(),
+            from_route_id: from_route.map(str::to_string),
+            to_route_id: to_route.map(str::to_string),
+            transfer_type: 2, // Minimum time
+            min_transfer_time: Some(self.walking_time),
+        };
+        [
+            transfer(&self.stop_id, partner_stop_id, self.route_id.as_deref(), partner_route_id),
+            transfer(partner_stop_id, &self.stop_id, partner_route_id, self.route_id.as_deref()),
+        ]
+    }
+}
+
+/// The links between our stops and one partner feed's.
+#[derive(Debug)]
+pub struct PartnerTransfers {
+    pub feed_id: String,
+    pub links: Vec<PartnerLink>,
+}
+
+/// Links between our stops and each partner's stops within walking distance. With
+/// `by_route`, there is a link for each pair of routes serving the two stops.
+pub fn build(
+    configs: &[(u64, PartnerConfig)],
+    config_path: &str,
+    stops: &[RawStop],
+    trips: &[Trip],
+    stop_times: &[StopTime],
+    diagnostics: &mut Diagnostics,
+) -> Vec<PartnerTransfers> {
+    let our_routes = routes_by_stop(
+        trips.iter().map(|t| (t.trip_id.as_str(), t.route_id.as_str())),
+        stop_times.iter().map(|st| (st.trip_id.as_str(), st.stop_id.as_str())),
+    );
+
+    let mut partners = Vec::new();
+    for (line, config) in configs {
+        let location = Location::line(config_path, *line);
+        let speed = config.walking_speed.unwrap_or(DEFAULT_WALKING_SPEED);
+        if config.max_walking_distance < 0.0 || speed <= 0.0 {
+            diagnostics.push(
+                Diagnostic::error("invalid_partner_config", "max_walking_distance and walking_speed must be positive")
+                    .at(location),
+            );
+            continue;
+        }
+
+        let feed: PartnerFeed = match partner::load(&config.path, diagnostics) {
+            Ok(feed) => feed,
+            Err(e) => {
+                diagnostics.push(
+                    Diagnostic::error("partner_feed_unreadable", format!("Partner feed {}: {}", config.feed_id, e))
+                        .at(location),
+                );
+                continue;
+            }
+        };
+        let partner_routes = routes_by_stop(
+            feed.trips.iter().map(|t| (t.trip_id.as_str(), t.route_id.as_str())),
+            feed.stop_times.iter().map(|st| (st.trip_id.as_str(), st.stop_id.as_str())),
+        );
+        let by_route = config.by_route == Some(1);
+
+        let mut links = Vec::new();
+        for stop in stops {
+            for (partner_stop, walking_time) in config.stops_within_walk(stop.stop_lat, stop.stop_lon, &feed.stops) {
+                let link = |route: Option<&str>, partner_route: Option<&str>| PartnerLink {
+                    stop_id: stop.stop_id.clone(),
+                    route_id: route.map(str::to_string),
+                    partner_stop_id: partner_stop.stop_id.clone(),
+                    partner_route_id: partner_route.map(str::to_string),
+                    walking_time,
+                };
+
+                if !by_route {
+                    links.push(link(None, None));
+                    continue;
+                }
+                let empty = BTreeSet::new();
+                let ours = our_routes.get(stop.stop_id.as_str()).unwrap_or(&empty);
+                let theirs = partner_routes.get(partner_stop.stop_id.as_str()).unwrap_or(&empty);
+                for our_route in ours {
+                    for their_route in theirs {
+                        links.push(link(Some(our_route), Some(their_route)));
+                    }
+                }
+            }
+        }
+
+        if links.is_empty() {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "no_partner_transfers",
+                    format!("No {} stops are within {} m of our stops", config.feed_id, config.max_walking_distance),
+                )
+                .at(location),
+            );
+        }
+        partners.push(PartnerTransfers { feed_id: config.feed_id.clone(), links });
+    }
+    partners
+}
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use crate::diagnostics::{Diagnostic, Diagnostics, Location};
+use crate::gtfs::{RawStop, StopTime, Transfer, Trip};
+
+/// Runs every check against the generated feed.
+pub fn validate(
+    trips: &[Trip],
+    stop_times: &[StopTime],
+    stops: &[RawStop],
+    diagnostics: &mut Diagnostics,
+) {
+    check_unique_trip_ids(trips, diagnostics);
+    check_accessible_trips(trips, stop_times, stops, diagnostics);
+}
+
+/// Trip ids must be unique across every route in the feed.
+fn check_unique_trip_ids(trips: &[Trip], diagnostics: &mut Diagnostics) {
+    let mut seen = HashSet::new();
+    for trip in trips {
+        if !seen.insert(trip.trip_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::error("duplicate_trip_id", format!("Trip id is used by more than one trip (route {})", trip.route_id))
+                    .at(Location::trip(&trip.trip_id)),
+            );
+        }
+    }
+}
+
+/// An accessible trip that serves a stop marked as not accessible (wheelchair_boarding = 2)
+/// is almost always a data mistake on one side or the other.
+fn check_accessible_trips(
+    trips: &[Trip],
+    stop_times: &[StopTime],
+    stops: &[RawStop],
+    diagnostics: &mut Diagnostics,
+) {
+    let inaccessible_stops: HashSet<&str> = stops
+        .iter()
+        .filter(|s| s.wheelchair_boarding == Some(2))
+        .map(|s| s.stop_id.as_str())
+        .collect();
+
+    if inaccessible_stops.is_empty() {
+        return;
+    }
+
+    let accessible_trips: HashSet<&str> = trips
+        .iter()
+        .filter(|t| t.wheelchair_accessible == 1)
+        .map(|t| t.trip_id.as_str())
+        .collect();
+
+    for st in stop_times {
+        if accessible_trips.contains(st.trip_id.as_str()) && inaccessible_stops.contains(st.stop_id.as_str()) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "accessible_trip_serves_inaccessible_stop",
+                    format!("Trip is wheelchair accessible but stop_sequence {} has wheelchair_boarding = 2", st.stop_sequence),
+                )
+                .at(Location::trip(&st.trip_id))
+                .stop(&st.stop_id),
+            );
+        }
+    }
+}
+
+/// Every stop and route a transfer refers to must be in the feed's stops.txt and
+/// routes.txt. `path` is the transfers.txt the rows are written to.
+pub fn check_transfer_references(
+    transfers: &[Transfer],
+    stop_ids: &HashSet<&str>,
+    route_ids: &HashSet<&str>,
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) {
+    for transfer in transfers {
+        for stop_id in [&transfer.from_stop_id, &transfer.to_stop_id] {
+            if !stop_ids.contains(stop_id.as_str()) {
+                diagnostics.push(
+                    Diagnostic::error("missing_transfer_stop", "Transfer references a stop which is not in stops.txt")
+                        .at(Location::file(path))
+                        .stop(stop_id),
+                );
+            }
+        }
+        for route_id in [&transfer.from_route_id, &transfer.to_route_id].into_iter().flatten() {
+            if !route_ids.contains(route_id.as_str()) {
+                diagnostics.push(
+                    Diagnostic::error(
+                        "missing_transfer_route",
+                        format!("Transfer references route {} which is not in routes.txt", route_id),
+                    )
+                    .at(Location::file(path)),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Tests for the `access` command.
+
+use std::fs;
+
+mod common;
+
+use common::{run, run_generator};
+
+#[test]
+fn access_writes_travel_time_matrix_and_isochrones() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let args = ["access", "--date", "20260105", "--start", "07:00", "--end", "08:00", "--step", "20", "--minutes", "10", "--walk", "300"];
+    let summary = run(workdir.path(), &args);
+    assert!(summary.contains("85 stops on 20260105, leaving 07:00:00-08:00:00: 7140 of 7140 stop pairs reachable"), "{}", summary);
+
+    let matrix = fs::read_to_string(workdir.path().join("access/matrix.csv")).unwrap();
+    let rows: Vec<Vec<&str>> = matrix.lines().map(|l| l.split(',').collect()).collect();
+    assert_eq!(rows[0][..4], ["from_stop_id", "from_stop_name", "157583", "157584"]);
+    // Dock 4 to the next stop is a minute; back again is the rest of the loop
+    assert_eq!(rows[1][..4], ["157583", "Irvine Station (Dock 4)", "0", "1"]);
+    assert_eq!(rows[2][..4], ["157584", "Ada @ Barranca Pkwy", "110", "0"]);
+
+    let geojson: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("access/isochrones.geojson")).unwrap()).unwrap();
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 85);
+    assert_eq!(features[0]["properties"], serde_json::json!({ "stop_id": "157583", "stop_name": "Irvine Station (Dock 4)", "minutes": 10 }));
+    assert_eq!(features[0]["geometry"]["type"], "MultiPolygon");
+    assert!(!features[0]["geometry"]["coordinates"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,97 @@
+//! Tests for chaining trips into blocks (`blocks::build`) and the `blocks` command.
+
+use std::fs;
+use std::process::Command;
+
+mod common;
+
+use irvine_connect_schedule_gtfs::blocks::{self, BlockRow};
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+
+use common::{feed, run_failing, run_generator};
+
+const STOPS: [(&str, f64, f64); 2] = [("X", 33.0, -117.0), ("Y", 33.01, -117.0)];
+
+/// Each built block's trip_ids, in order.
+fn chains(rows: &[BlockRow]) -> Vec<Vec<&str>> {
+    let mut chains: Vec<(&str, Vec<&str>)> = Vec::new();
+    for row in rows {
+        match chains.last_mut() {
+            Some((block_id, trips)) if *block_id == row.block_id => trips.push(&row.trip_id),
+            _ => chains.push((&row.block_id, vec![&row.trip_id])),
+        }
+    }
+    chains.into_iter().map(|(_, trips)| trips).collect()
+}
+
+#[test]
+fn blocks_chain_trips_into_the_fewest_vehicles() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_irvine-connect-schedule-gtfs"))
+        .arg("blocks")
+        .current_dir(workdir.path())
+        .output()
+        .unwrap();
+    let summary = String::from_utf8_lossy(&output.stdout);
+    // Buses 1 and 4 share block 0520 and buses 2 and 5 share 0535, so six vehicles run in four manual blocks
+    assert!(summary.contains("Weekday: 44 trips in 6 blocks (4 manual blocks, 26 trips conflicting"), "{}", summary);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning[block_conflict]: Trip in block 0520 starts at 06:00:00, before trip 5956_weekday_1_1 ends at 07:50:00"), "{}", stderr);
+
+    let rows = fs::read_to_string(workdir.path().join("blocks.csv")).unwrap();
+    assert!(rows.contains("\nWeekday,Weekday_2,5956_weekday_4_22,06:00:00,06:45:00,157625,157583,,0520\n"), "{}", rows);
+    assert!(rows.contains("\nWeekday,Weekday_2,5956_weekday_4_23,07:00:00,08:50:00,157583,157583,15,0520\n"), "{}", rows);
+}
+
+#[test]
+fn trips_are_reassigned_when_that_saves_a_block() {
+    // t1 would rather run t4 of its manual block, but t4 is the only trip t2 can
+    // run next, so t1 takes t3 instead
+    let feed = feed(
+        &STOPS,
+        &[
+            ("t1", "Weekday", "A", &[("X", "08:00:00"), ("Y", "08:30:00")]),
+            ("t2", "Weekday", "B", &[("X", "08:20:00"), ("Y", "08:50:00")]),
+            ("t3", "Weekday", "B", &[("Y", "08:40:00"), ("X", "09:10:00")]),
+            ("t4", "Weekday", "A", &[("Y", "09:00:00"), ("X", "09:30:00")]),
+        ],
+    );
+    let mut diagnostics = Diagnostics::new();
+
+    let (rows, comparisons) = blocks::build(&feed, 0, &mut diagnostics);
+    assert_eq!(chains(&rows), [vec!["t1", "t3"], vec!["t2", "t4"]]);
+    assert_eq!((comparisons[0].blocks, comparisons[0].manual_blocks, comparisons[0].manual_conflicts), (2, 2, 1));
+}
+
+#[test]
+fn trips_of_the_same_manual_block_are_chained_when_it_costs_nothing() {
+    // t3 is the earliest trip after both t1 and t2, but t1 and t4 share a block
+    let feed = feed(
+        &STOPS,
+        &[
+            ("t1", "Weekday", "A", &[("X", "08:00:00"), ("Y", "08:30:00")]),
+            ("t2", "Weekday", "B", &[("X", "08:05:00"), ("Y", "08:35:00")]),
+            ("t3", "Weekday", "B", &[("Y", "09:00:00"), ("X", "09:30:00")]),
+            ("t4", "Weekday", "A", &[("Y", "09:10:00"), ("X", "09:40:00")]),
+        ],
+    );
+    let mut diagnostics = Diagnostics::new();
+
+    let (rows, comparisons) = blocks::build(&feed, 0, &mut diagnostics);
+    assert_eq!(chains(&rows), [vec!["t1", "t4"], vec!["t2", "t3"]]);
+    assert_eq!((comparisons[0].blocks, comparisons[0].manual_conflicts), (2, 0));
+
+    // With a 35 minute layover neither can run t3, so it needs a vehicle of its own
+    let (rows, _) = blocks::build(&feed, 35 * 60, &mut diagnostics);
+    assert_eq!(chains(&rows), [vec!["t1", "t4"], vec!["t2"], vec!["t3"]]);
+}
+
+#[test]
+fn negative_min_layover_is_rejected() {
+    let workdir = tempfile::tempdir().unwrap();
+    let stderr = run_failing(workdir.path(), &["blocks", "--min-layover", "-5"]);
+    assert!(stderr.starts_with("Invalid number of minutes: -5\nUsage: "), "{}", stderr);
+    assert!(!workdir.path().join("blocks.csv").exists());
+}
@@ -0,0 +1,60 @@
+//! Tests for working out each schedule version's dates in the feed
+//! (`calendar::feed_periods`).
+
+use irvine_connect_schedule_gtfs::calendar::{feed_periods, parse_date};
+use irvine_connect_schedule_gtfs::data::ScheduleVersion;
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+
+fn version(name: &'static str, from: &'static str, until: Option<&'static str>) -> ScheduleVersion {
+    ScheduleVersion { name, effective_from: from, effective_until: until, routes: &[] }
+}
+
+fn date(s: &str) -> chrono::NaiveDate {
+    parse_date(s).unwrap()
+}
+
+fn codes(diagnostics: &Diagnostics) -> Vec<&'static str> {
+    diagnostics.iter().map(|d| d.code).collect()
+}
+
+#[test]
+fn open_ended_version_runs_until_the_next_one() {
+    let versions = [version("2026Spring", "20260105", None), version("2026Fall", "20260824", Some("20261231"))];
+    let mut diagnostics = Diagnostics::new();
+
+    let periods = feed_periods(&versions, None, date("20260101"), &mut diagnostics).unwrap();
+    assert_eq!(
+        periods,
+        vec![
+            Some((date("20260105"), date("20260823"))),
+            Some((date("20260824"), date("20261231"))),
+        ]
+    );
+    assert!(!diagnostics.has_errors());
+    assert_eq!(versions[1].service_id("Weekday"), "Weekday_2026Fall");
+}
+
+#[test]
+fn overlapping_versions_are_rejected() {
+    let versions = [
+        version("2026Spring", "20260105", Some("20260830")),
+        version("2026Fall", "20260824", Some("20261231")),
+    ];
+    let mut diagnostics = Diagnostics::new();
+
+    assert!(feed_periods(&versions, None, date("20260101"), &mut diagnostics).is_none());
+    assert_eq!(codes(&diagnostics), ["overlapping_schedule_versions"]);
+}
+
+#[test]
+fn rolling_window_leaves_out_versions_outside_it() {
+    let versions = [version("2026Spring", "20260105", None), version("2026Fall", "20260824", None)];
+    let mut diagnostics = Diagnostics::new();
+
+    let periods = feed_periods(&versions, Some(3), date("20260901"), &mut diagnostics).unwrap();
+    assert_eq!(periods, vec![None, Some((date("20260901"), date("20261201")))]);
+
+    // Without a window the last version has no end
+    assert!(feed_periods(&versions, None, date("20260901"), &mut diagnostics).is_none());
+    assert_eq!(codes(&diagnostics), ["no_feed_end_date"]);
+}
@@ -0,0 +1,220 @@
+//! Helpers shared by the integration tests: running the binary on
+//! `tests/fixtures/input` in a temporary directory, and small hand-built feeds.
+
+// Each test file uses only some of these
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use irvine_connect_schedule_gtfs::generate::Feed;
+use irvine_connect_schedule_gtfs::gtfs::{Calendar, RawStop, StopTime, Trip};
+use irvine_connect_schedule_gtfs::partner::{PartnerFeed, PartnerStop, PartnerStopTime, PartnerTrip};
+
+pub fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), &target).unwrap();
+        }
+    }
+}
+
+pub fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Describes the first line where `actual` differs from `expected`.
+pub fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (e, a) => {
+                return format!(
+                    "line {}:\n  expected: {}\n  actual:   {}",
+                    line,
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                );
+            }
+        }
+    }
+}
+
+pub fn run_generator(workdir: &Path, args: &[&str]) {
+    run_generator_with(workdir, args, &[]);
+}
+
+/// Runs the generator with some fixture input files replaced by `(path, content)`.
+pub fn run_generator_with(workdir: &Path, args: &[&str], input_files: &[(&str, &str)]) {
+    copy_dir(&fixtures_dir().join("input"), &workdir.join("input"));
+    for (path, content) in input_files {
+        fs::write(workdir.join("input").join(path), content).unwrap();
+    }
+
+    // Pin "today" so expiry warnings and rolling windows don't depend on the clock
+    let mut args_with_today = vec!["--today", "20260101"];
+    args_with_today.extend(args);
+    run(workdir, &args_with_today);
+}
+
+/// Runs the binary in `workdir` and returns its stdout.
+pub fn run(workdir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_irvine-connect-schedule-gtfs"))
+        .args(args)
+        .current_dir(workdir)
+        .output()
+        .expect("failed to run generator");
+
+    assert!(
+        output.status.success(),
+        "generator failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Runs the binary in `workdir`, expecting it to fail, and returns its stderr.
+pub fn run_failing(workdir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_irvine-connect-schedule-gtfs"))
+        .args(args)
+        .current_dir(workdir)
+        .output()
+        .expect("failed to run generator");
+    assert!(!output.status.success(), "generator succeeded:\n{}", String::from_utf8_lossy(&output.stdout));
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// `(trip_id, service_id, block_id, [(stop_id, time)])`
+pub type FeedTrip<'a> = (&'a str, &'a str, &'a str, &'a [(&'a str, &'a str)]);
+
+/// A feed of route "R" with `trips`, each leaving every stop at the time it arrives,
+/// and every service running daily in 2026. `stops` are `(stop_id, lat, lon)`, named after their ids.
+pub fn feed(stops: &[(&str, f64, f64)], trips: &[FeedTrip]) -> Feed {
+    let mut services: Vec<&str> = trips.iter().map(|(_, service_id, ..)| *service_id).collect();
+    services.sort();
+    services.dedup();
+    Feed {
+        agencies: Vec::new(),
+        routes: Vec::new(),
+        fares: None,
+        calendars: services
+            .iter()
+            .map(|service_id| Calendar {
+                service_id: service_id.to_string(),
+                monday: 1,
+                tuesday: 1,
+                wednesday: 1,
+                thursday: 1,
+                friday: 1,
+                saturday: 1,
+                sunday: 1,
+                start_date: "20260101".to_string(),
+                end_date: "20261231".to_string(),
+            })
+            .collect(),
+        calendar_dates: Vec::new(),
+        stops: stops
+            .iter()
+            .map(|(stop_id, lat, lon)| RawStop {
+                stop_id: stop_id.to_string(),
+                stop_name: stop_id.to_string(),
+                stop_lat: *lat,
+                stop_lon: *lon,
+                wheelchair_boarding: None,
+            })
+            .collect(),
+        shapes: Vec::new(),
+        trips: trips
+            .iter()
+            .map(|(trip_id, service_id, block_id, _)| Trip {
+                route_id: "R".to_string(),
+                service_id: service_id.to_string(),
+                trip_id: trip_id.to_string(),
+                shape_id: String::new(),
+                block_id: block_id.to_string(),
+                wheelchair_accessible: 1,
+                bikes_allowed: 1,
+            })
+            .collect(),
+        stop_times: trips
+            .iter()
+            .flat_map(|(trip_id, _, _, times)| {
+                times.iter().enumerate().map(|(i, (stop_id, time))| StopTime {
+                    trip_id: trip_id.to_string(),
+                    arrival_time: time.to_string(),
+                    departure_time: time.to_string(),
+                    stop_id: stop_id.to_string(),
+                    stop_sequence: i as u32 + 1,
+                    stop_headsign: String::new(),
+                    timepoint: 1,
+                })
+            })
+            .collect(),
+        schedules: Vec::new(),
+    }
+}
+
+/// A partner feed of the same stops and trips as `feed`.
+pub fn partner_feed(stops: &[(&str, f64, f64)], trips: &[FeedTrip]) -> PartnerFeed {
+    let feed = feed(stops, trips);
+    PartnerFeed {
+        stops: feed
+            .stops
+            .iter()
+            .map(|s| PartnerStop {
+                stop_id: s.stop_id.clone(),
+                stop_name: Some(s.stop_name.clone()),
+                stop_lat: Some(s.stop_lat),
+                stop_lon: Some(s.stop_lon),
+                location_type: None,
+            })
+            .collect(),
+        trips: feed
+            .trips
+            .iter()
+            .map(|t| PartnerTrip {
+                route_id: t.route_id.clone(),
+                service_id: t.service_id.clone(),
+                trip_id: t.trip_id.clone(),
+                trip_headsign: None,
+                direction_id: None,
+            })
+            .collect(),
+        stop_times: feed
+            .stop_times
+            .iter()
+            .map(|st| PartnerStopTime {
+                trip_id: st.trip_id.clone(),
+                arrival_time: Some(st.arrival_time.clone()),
+                departure_time: Some(st.departure_time.clone()),
+                stop_id: st.stop_id.clone(),
+                stop_sequence: st.stop_sequence,
+            })
+            .collect(),
+        calendars: feed.calendars,
+        calendar_dates: feed.calendar_dates,
+    }
+}
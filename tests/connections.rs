@@ -0,0 +1,25 @@
+//! Tests for the `connections` command.
+
+use std::fs;
+
+mod common;
+
+use common::{fixtures_dir, run, run_generator_with};
+
+#[test]
+fn connections_report_waits_and_missed_trains() {
+    let workdir = tempfile::tempdir().unwrap();
+    let metrolink = fixtures_dir().join("partners/metrolink");
+    let config = format!("feed_id,path,id_prefix,max_walking_distance\nmetrolink,{},,200\n", metrolink.display());
+    run_generator_with(workdir.path(), &[], &[("partner_feeds.txt", &config)]);
+
+    let summary = run(workdir.path(), &["connections", "--date", "20261019", "--window", "30"]);
+    assert!(summary.contains("Weekday (20261019), bus_to_train: 1 of 41 connections"), "{}", summary);
+    assert!(summary.contains("Weekend (20261024)"), "{}", summary);
+
+    let rows = fs::read_to_string(workdir.path().join("connections.csv")).unwrap();
+    // Arrives 07:25, one minute's walk, train 601 leaves 07:41
+    assert!(rows.contains("Weekday,20261019,bus_to_train,5956_weekday_6_38,07:25:00,91,,601,07:41:00,15,,,"), "{}", rows);
+    // Arrives 07:50, ten minutes after train 601 left
+    assert!(rows.contains("Weekday,20261019,bus_to_train,5956_weekday_1_1,07:50:00,91,,,,,601,07:41:00,10"), "{}", rows);
+}
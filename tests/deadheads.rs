@@ -0,0 +1,100 @@
+//! Tests for pull-outs and pull-ins (`deadheads::compute`) and the `deadheads`
+//! command.
+
+use std::fs;
+
+mod common;
+
+use irvine_connect_schedule_gtfs::blocks::BlockRow;
+use irvine_connect_schedule_gtfs::deadheads::{self, Garage};
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+use irvine_connect_schedule_gtfs::gtfs::RawStop;
+
+use common::{run, run_generator};
+
+fn garage(garage_id: &str, lat: f64, lon: f64) -> Garage {
+    Garage {
+        garage_id: garage_id.to_string(),
+        garage_name: garage_id.to_string(),
+        garage_lat: lat,
+        garage_lon: lon,
+        speed_mph: None,
+        circuity: None,
+    }
+}
+
+fn stop(stop_id: &str, lat: f64, lon: f64) -> RawStop {
+    RawStop { stop_id: stop_id.to_string(), stop_name: stop_id.to_string(), stop_lat: lat, stop_lon: lon, wheelchair_boarding: None }
+}
+
+/// A trip of block `block_id` from stop X in the south to stop Y in the north.
+fn trip_north(trip_id: &str, block_id: &str, start_time: &str, end_time: &str) -> BlockRow {
+    BlockRow {
+        service_id: "Weekday".to_string(),
+        block_id: "Weekday_1".to_string(),
+        trip_id: trip_id.to_string(),
+        start_time: start_time.to_string(),
+        end_time: end_time.to_string(),
+        start_stop_id: "X".to_string(),
+        end_stop_id: "Y".to_string(),
+        layover_minutes: None,
+        manual_block_id: block_id.to_string(),
+    }
+}
+
+#[test]
+fn deadheads_pull_out_to_the_first_stop_and_write_ods_files() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let summary = run(workdir.path(), &["deadheads", "--ods", "gtfs"]);
+    // Block 0550 starts with a StartYale trip, the farthest from the yard
+    assert!(summary.contains("Weekday block 0550: pulls out of yard at 06:24:00 (16 min to stop 157625), pulls in to yard at 19:23:00 (3 min from stop 157583); 9.5% non-revenue"), "{}", summary);
+
+    let rows = fs::read_to_string(workdir.path().join("deadheads.csv")).unwrap();
+    assert!(rows.contains("\nWeekday,0600,yard,06:37:00,157583,3,0.68,5956_weekday_3_15,5956_weekday_3_21,157625,16,5.02,19:56:00,yard,725,55,74,9.26\n"), "{}", rows);
+    let deadheads = fs::read_to_string(workdir.path().join("gtfs/deadheads.txt")).unwrap();
+    assert!(deadheads.contains("\nWeekday_0550_pull_out,Weekday,0550,5956_weekday_6_38,\n"), "{}", deadheads);
+    let times = fs::read_to_string(workdir.path().join("gtfs/deadhead_times.txt")).unwrap();
+    assert!(times.contains("\nWeekday_0550_pull_out,06:24:00,06:24:00,yard,,1\nWeekday_0550_pull_out,06:40:00,06:40:00,,157625,2\n"), "{}", times);
+}
+
+#[test]
+fn blocks_pull_in_to_the_garage_nearest_their_last_stop() {
+    let garages = [garage("south", 33.0, -117.001), garage("north", 33.1, -117.001)];
+    let stops = [stop("X", 33.0, -117.0), stop("Y", 33.1, -117.0)];
+    let rows = [trip_north("t1", "1", "08:00:00", "08:30:00")];
+    let mut diagnostics = Diagnostics::new();
+
+    let deadheads = deadheads::compute(&garages, &rows, &stops, &mut diagnostics);
+    assert_eq!(deadheads.len(), 1);
+    let d = &deadheads[0];
+    assert_eq!((d.pull_out_garage_id.as_str(), d.pull_out_time.as_str()), ("south", "07:59:00"));
+    assert_eq!((d.pull_in_garage_id.as_str(), d.pull_in_time.as_str()), ("north", "08:31:00"));
+    assert_eq!(diagnostics.iter().count(), 0);
+
+    let dir = tempfile::tempdir().unwrap();
+    deadheads::write_ods(&garages, &deadheads, &rows, dir.path().to_str().unwrap()).unwrap();
+    let times = fs::read_to_string(dir.path().join("deadhead_times.txt")).unwrap();
+    assert!(times.contains("\nWeekday_1_pull_out,07:59:00,07:59:00,south,,1\n"), "{}", times);
+    assert!(times.contains("\nWeekday_1_pull_in,08:31:00,08:31:00,north,,2\n"), "{}", times);
+}
+
+#[test]
+fn blocks_pulling_out_before_midnight_are_reported_and_left_out() {
+    let garages = [garage("south", 33.0, -117.001)];
+    let stops = [stop("X", 33.0, -117.0), stop("Y", 33.1, -117.0)];
+    let rows = [trip_north("t1", "1", "00:00:00", "00:30:00"), trip_north("t2", "2", "00:01:00", "00:31:00")];
+    let mut diagnostics = Diagnostics::new();
+
+    // The pull-out takes a minute, so only block 2 can start after midnight
+    let deadheads = deadheads::compute(&garages, &rows, &stops, &mut diagnostics);
+    let blocks: Vec<(&str, &str)> = deadheads.iter().map(|d| (d.block_id.as_str(), d.pull_out_time.as_str())).collect();
+    assert_eq!(blocks, [("2", "00:00:00")]);
+    let messages: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code, d.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        [("pull_out_before_midnight", "Block 1 would pull out of south 1 min before midnight to start at 00:00:00; its deadheads are left out")]
+    );
+    assert!(diagnostics.has_errors());
+}
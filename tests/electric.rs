@@ -0,0 +1,110 @@
+//! Tests for the battery simulation (`electric::simulate`) and the `electric`
+//! command.
+
+use std::fs;
+
+mod common;
+
+use irvine_connect_schedule_gtfs::blocks::BlockRow;
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+use irvine_connect_schedule_gtfs::electric::{self, Charger, ElectricConfig, EnergyRate, Vehicle};
+use irvine_connect_schedule_gtfs::stats::TripStats;
+
+use common::{run, run_generator};
+
+fn block_row(trip_id: &str, start_time: &str, end_time: &str) -> BlockRow {
+    BlockRow {
+        service_id: "Weekday".to_string(),
+        block_id: "Weekday_1".to_string(),
+        trip_id: trip_id.to_string(),
+        start_time: start_time.to_string(),
+        end_time: end_time.to_string(),
+        start_stop_id: "dock".to_string(),
+        end_stop_id: "dock".to_string(),
+        layover_minutes: None,
+        manual_block_id: "1".to_string(),
+    }
+}
+
+fn trip_stats(trip_id: &str, revenue_miles: f64) -> TripStats {
+    TripStats {
+        trip_id: trip_id.to_string(),
+        route_id: "R".to_string(),
+        service_id: "Weekday".to_string(),
+        block_id: "1".to_string(),
+        start_time: String::new(),
+        end_time: String::new(),
+        revenue_hours: 1.0,
+        revenue_miles,
+    }
+}
+
+fn config() -> ElectricConfig {
+    ElectricConfig {
+        vehicle: Vehicle { battery_kwh: 100.0, initial_soc: Some(0.5), min_soc: 0.1 },
+        rates: vec![EnergyRate { max_speed_mph: None, kwh_per_mile: 1.0 }],
+        chargers: vec![Charger { stop_id: "dock".to_string(), power_kw: 600.0 }],
+    }
+}
+
+#[test]
+fn electric_blocks_report_state_of_charge_and_where_to_charge() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let summary = run(workdir.path(), &["electric"]);
+    // Block 0520 holds two buses' trips, so it isn't simulated as one battery
+    assert!(!summary.contains("block 0520"), "{}", summary);
+    assert!(summary.contains("Weekday block 0550: feasible, lowest charge 46.3%"), "{}", summary);
+
+    let summary = run(workdir.path(), &["electric", "--built-blocks"]);
+    assert!(summary.contains("Weekday block Weekday_1: feasible, lowest charge 42.0%"), "{}", summary);
+    let blocks = fs::read_to_string(workdir.path().join("electric/blocks.csv")).unwrap();
+    assert!(blocks.contains("\nWeekday,Weekday_1,7,186.9,392.49,137.5,42.05,true,,,,0.0\n"), "{}", blocks);
+    // Ten minutes at the 150 kW Dock 4 charger add 25 kWh before the next loop
+    let trips = fs::read_to_string(workdir.path().join("electric/trips.csv")).unwrap();
+    assert!(trips.contains("\nWeekday,Weekday_1,5956_weekday_1_2,08:00:00,09:50:00,26.7,14.56,56.07,25.0,408.93,352.86,false\n"), "{}", trips);
+}
+
+#[test]
+fn layover_charging_stops_at_a_full_battery() {
+    let config = config();
+    let rows = [block_row("t1", "08:00:00", "09:00:00"), block_row("t2", "10:00:00", "11:00:00")];
+    let stats = [trip_stats("t1", 10.0), trip_stats("t2", 10.0)];
+
+    let (trips, blocks) = electric::simulate(&config, &rows, &stats, false, &mut Diagnostics::new());
+    // An hour at 600 kW could add 600 kWh, but the battery only has room for 60
+    assert_eq!((trips[0].soc_start_kwh, trips[0].soc_end_kwh), (50.0, 40.0));
+    assert_eq!((trips[1].charged_kwh, trips[1].soc_start_kwh, trips[1].soc_end_kwh), (60.0, 100.0, 90.0));
+    assert_eq!(blocks[0].charged_kwh, 60.0);
+    assert!(blocks[0].feasible);
+}
+
+#[test]
+fn blocks_with_overlapping_trips_are_reported_instead_of_simulated() {
+    let rows = [block_row("t1", "08:00:00", "09:00:00"), block_row("t2", "08:30:00", "09:30:00")];
+    let stats = [trip_stats("t1", 10.0), trip_stats("t2", 10.0)];
+
+    let mut diagnostics = Diagnostics::new();
+    let (trips, blocks) = electric::simulate(&config(), &rows, &stats, true, &mut diagnostics);
+    assert!(trips.is_empty() && blocks.is_empty());
+    let messages: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code, d.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        vec![("block_conflict", "Trip in block 1 starts at 08:30:00, before trip t1 ends at 09:00:00; the block is not simulated")]
+    );
+}
+
+#[test]
+fn a_battery_without_capacity_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("vehicle.txt"), "battery_kwh,initial_soc,min_soc\n0,1,0.1\n").unwrap();
+    fs::write(dir.path().join("energy.txt"), "max_speed_mph,kwh_per_mile\n,2.0\n").unwrap();
+
+    let dir = dir.path().to_string_lossy();
+    let error = electric::load(&dir, &[], &mut Diagnostics::new()).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        format!("{}/vehicle.txt:2: battery_kwh must be positive and initial_soc and min_soc between 0 and 1", dir)
+    );
+}
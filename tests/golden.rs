@@ -0,0 +1,114 @@
+//! Golden-file regression tests for the generated feed.
+//!
+//! The generator is run against `tests/fixtures/input` in a temporary directory and every
+//! file it writes is compared with `tests/golden/`. After an intended schedule change,
+//! regenerate the goldens with:
+//!
+//! ```bash
+//! BLESS=1 cargo test --test golden
+//! ```
+//!
+//! and review the diff before committing.
+
+use std::fs;
+
+mod common;
+
+use common::{copy_dir, file_names, first_difference, golden_dir, run_generator};
+
+#[test]
+fn generated_feed_matches_golden_files() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let output_dir = workdir.path().join("gtfs");
+    let golden_dir = golden_dir();
+
+    if std::env::var_os("BLESS").is_some() {
+        if golden_dir.exists() {
+            fs::remove_dir_all(&golden_dir).unwrap();
+        }
+        copy_dir(&output_dir, &golden_dir);
+        return;
+    }
+
+    let produced = file_names(&output_dir);
+    let expected = file_names(&golden_dir);
+    assert_eq!(produced, expected, "set of generated files changed (run with BLESS=1 if intended)");
+
+    let mut failures = Vec::new();
+    for name in &expected {
+        let expected_content = fs::read_to_string(golden_dir.join(name)).unwrap();
+        let actual_content = fs::read_to_string(output_dir.join(name)).unwrap();
+        if expected_content != actual_content {
+            failures.push(format!("{} differs at {}", name, first_difference(&expected_content, &actual_content)));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "generated feed differs from tests/golden (run with BLESS=1 if intended):\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn report_describes_every_generated_file() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &["--report", "report.json"]);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["status"], "success");
+    assert_eq!(report["errors"], 0);
+
+    let output_dir = workdir.path().join("gtfs");
+    let files = report["files"].as_array().unwrap();
+    let names: Vec<&str> = files.iter().map(|f| f["name"].as_str().unwrap()).collect();
+    assert_eq!(names, file_names(&output_dir));
+
+    for file in files {
+        let content = fs::read_to_string(output_dir.join(file["name"].as_str().unwrap())).unwrap();
+        assert_eq!(file["rows"], content.lines().count() - 1, "{}", file["name"]);
+        assert_eq!(file["sha256"].as_str().unwrap().len(), 64);
+    }
+
+    let services = report["services"].as_array().unwrap();
+    let trips: u64 = services.iter().map(|s| s["trips"].as_u64().unwrap()).sum();
+    let trips_txt = fs::read_to_string(output_dir.join("trips.txt")).unwrap();
+    assert_eq!(trips as usize, trips_txt.lines().count() - 1);
+}
+
+#[test]
+fn report_leaves_out_files_from_earlier_runs() {
+    let workdir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(workdir.path().join("gtfs")).unwrap();
+    fs::write(workdir.path().join("gtfs/transfers.txt"), "from_stop_id,to_stop_id\n").unwrap();
+    run_generator(workdir.path(), &["--report", "report.json"]);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("report.json")).unwrap()).unwrap();
+    let names: Vec<&str> = report["files"].as_array().unwrap().iter().map(|f| f["name"].as_str().unwrap()).collect();
+    assert!(!names.contains(&"transfers.txt"), "{:?}", names);
+    assert!(names.contains(&"trips.txt"), "{:?}", names);
+}
+
+#[test]
+fn rolling_window_is_clipped_to_the_effective_period() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(
+        workdir.path(),
+        &["--today", "20261215", "--rolling-window", "12", "--report", "report.json"],
+    );
+
+    let calendar = fs::read_to_string(workdir.path().join("gtfs/calendar.txt")).unwrap();
+    for row in calendar.lines().skip(1) {
+        assert!(row.ends_with(",20261215,20261231"), "{}", row);
+    }
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["feed_start_date"], "20261215");
+    let codes: Vec<&str> = report["diagnostics"].as_array().unwrap().iter().map(|d| d["code"].as_str().unwrap()).collect();
+    assert!(codes.contains(&"feed_expiring"), "{:?}", codes);
+}
@@ -0,0 +1,56 @@
+//! Tests for stop headways (`headways::compute`) and the `headways` command.
+
+use std::fs;
+
+mod common;
+
+use irvine_connect_schedule_gtfs::headways;
+
+use common::{feed, run, run_generator};
+
+#[test]
+fn headways_report_span_gaps_and_bunching_at_every_stop() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let summary = run(workdir.path(), &["headways", "--bunching", "11"]);
+    assert!(summary.contains("85 stops;"), "{}", summary);
+
+    let headways = workdir.path().join("headways");
+    let spans = fs::read_to_string(headways.join("spans.csv")).unwrap();
+    assert!(spans.contains("\n157583,Irvine Station (Dock 4),Weekday,41,06:00:00,19:15:00,30,1\n"), "{}", spans);
+
+    let hourly = fs::read_to_string(headways.join("hourly.csv")).unwrap();
+    assert!(hourly.contains("\n157583,Weekday,6,3,20.0\n"), "{}", hourly);
+
+    // Bus 6 leaves Dock 4 at 11:40 and bus 1 ten minutes later
+    let bunching = fs::read_to_string(headways.join("bunching.csv")).unwrap();
+    assert!(
+        bunching.contains("\n157583,Weekday,5956_weekday_6_41,0550,11:40:00,5956_weekday_1_4,0520,11:50:00,10.0\n"),
+        "{}",
+        bunching
+    );
+}
+
+#[test]
+fn departures_exactly_the_threshold_apart_are_not_bunched() {
+    let stops = [("S", 33.0, -117.0), ("E", 33.01, -117.0)];
+    let feed = feed(
+        &stops,
+        &[
+            ("t1", "Weekday", "b1", &[("S", "08:00:00"), ("E", "08:10:00")]),
+            ("t2", "Weekday", "b2", &[("S", "08:03:00"), ("E", "08:13:00")]),
+        ],
+    );
+
+    let at_threshold = headways::compute(&feed, 180);
+    assert!(at_threshold.bunching.is_empty());
+    assert_eq!(at_threshold.spans.len(), 1, "the last stop of a trip is not a departure");
+    assert_eq!(at_threshold.spans[0].bunched, 0);
+
+    let above = headways::compute(&feed, 181);
+    assert_eq!(above.bunching.len(), 1);
+    let row = &above.bunching[0];
+    assert_eq!((row.trip_id.as_str(), row.next_trip_id.as_str(), row.gap_minutes), ("t1", "t2", 3.0));
+    assert_eq!(above.spans[0].bunched, 1);
+}
@@ -0,0 +1,217 @@
+//! Property tests for pattern expansion (`data::process_trips`) and stop time
+//! interpolation (`interpolate::trip_stop_times`).
+
+use proptest::prelude::*;
+
+use irvine_connect_schedule_gtfs::data::{Pattern, RawTrip, RouteSchedule, Timepoint, process_trips};
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+use irvine_connect_schedule_gtfs::gtfs::{RawStop, StopTime};
+use irvine_connect_schedule_gtfs::interpolate::{match_timepoints, trip_stop_times};
+use irvine_connect_schedule_gtfs::time::parse_time;
+
+/// A randomly generated route: the stop sequence, the positions in it used as
+/// timepoints, and the minutes from the first timepoint to each timepoint.
+#[derive(Debug, Clone)]
+struct Case {
+    sequence: Vec<u32>,
+    timepoint_positions: Vec<usize>,
+    offsets: Vec<i64>,
+    first: usize,
+    last: usize,
+    start_minutes: i64,
+}
+
+fn case() -> impl Strategy<Value = Case> {
+    // Stop ids come from a small pool so the sequence repeats stops (like Dock 4)
+    (prop::collection::vec(100u32..110, 2..60), 2usize..8)
+        .prop_flat_map(|(sequence, max_timepoints)| {
+            let len = sequence.len();
+            let count = max_timepoints.min(len);
+            (
+                Just(sequence),
+                prop::sample::subsequence((0..len).collect::<Vec<_>>(), 2..=count),
+            )
+        })
+        .prop_flat_map(|(sequence, timepoint_positions)| {
+            let count = timepoint_positions.len();
+            (
+                Just(sequence),
+                Just(timepoint_positions),
+                prop::collection::vec(0i64..30, count - 1),
+                (0..count - 1).prop_flat_map(move |first| (Just(first), first + 1..count)),
+                0i64..27 * 60,
+            )
+        })
+        .prop_map(|(sequence, timepoint_positions, increments, (first, last), start_minutes)| {
+            let mut offsets = vec![0];
+            for inc in increments {
+                offsets.push(offsets.last().unwrap() + inc);
+            }
+            Case { sequence, timepoint_positions, offsets, first, last, start_minutes }
+        })
+}
+
+fn raw_stop(id: u32) -> RawStop {
+    RawStop {
+        stop_id: id.to_string(),
+        stop_name: format!("Stop {}", id),
+        stop_lat: 33.65,
+        stop_lon: -117.73,
+        wheelchair_boarding: Some(1),
+    }
+}
+
+/// Runs both stages for a single trip and returns its timepoints and stop times.
+fn generate(case: &Case) -> (Vec<(u32, Option<String>)>, Vec<StopTime>) {
+    // Stops repeat in the random sequences, so name the visit each timepoint refers to
+    let timepoints: Vec<Timepoint> = case
+        .timepoint_positions
+        .iter()
+        .map(|&p| {
+            let stop_id = case.sequence[p];
+            let occurrence = case.sequence[..=p].iter().filter(|&&id| id == stop_id).count();
+            Timepoint::nth(stop_id, occurrence)
+        })
+        .collect();
+    let start_time = format!("{:02}:{:02}", case.start_minutes / 60, case.start_minutes % 60);
+
+    let mut diagnostics = Diagnostics::new();
+    let mut trips = Vec::new();
+    process_trips(
+        &mut trips,
+        vec![RawTrip {
+            bus_id: 1,
+            block_id: "1",
+            start_time: Box::leak(start_time.into_boxed_str()),
+            pattern: Pattern { first: case.first, last: case.last, headsign: None },
+        }],
+        "Weekday",
+        "R",
+        &timepoints,
+        &case.offsets,
+        &mut diagnostics,
+    );
+
+    let route = RouteSchedule {
+        route_id: "R",
+        shape_id: "S",
+        timepoints: timepoints.clone(),
+        offsets: case.offsets.clone(),
+        loop_length: case.sequence.len(),
+        headsigns: &[],
+        trips,
+    };
+
+    let stops: Vec<RawStop> = case.sequence.iter().map(|&id| raw_stop(id)).collect();
+    let sequence: Vec<&RawStop> = stops.iter().collect();
+
+    let trip = &route.trips[0];
+    let key_indices = match_timepoints(&route.timepoints[case.first..=case.last], &sequence).unwrap();
+    let stop_times = trip_stop_times(trip, &route, &sequence, &key_indices, &mut diagnostics);
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics);
+    (trip.stops.clone(), stop_times)
+}
+
+fn seconds(time: &str) -> i64 {
+    parse_time(time).unwrap_or_else(|| panic!("invalid GTFS time {}", time))
+}
+
+proptest! {
+    #[test]
+    fn pattern_expansion_covers_pattern_timepoints(case in case()) {
+        let (timepoints, _) = generate(&case);
+
+        prop_assert_eq!(timepoints.len(), case.last - case.first + 1);
+        let start = case.start_minutes * 60;
+        for (i, (_, time)) in timepoints.iter().enumerate() {
+            let expected = start + (case.offsets[case.first + i] - case.offsets[case.first]) * 60;
+            prop_assert_eq!(seconds(time.as_deref().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn times_never_decrease(case in case()) {
+        let (_, stop_times) = generate(&case);
+
+        for pair in stop_times.windows(2) {
+            prop_assert!(
+                seconds(&pair[0].departure_time) <= seconds(&pair[1].arrival_time),
+                "{:?} then {:?}", pair[0], pair[1]
+            );
+        }
+        for st in &stop_times {
+            prop_assert!(seconds(&st.arrival_time) <= seconds(&st.departure_time));
+        }
+    }
+
+    #[test]
+    fn stop_sequence_strictly_increases(case in case()) {
+        let (_, stop_times) = generate(&case);
+
+        for pair in stop_times.windows(2) {
+            prop_assert!(pair[0].stop_sequence < pair[1].stop_sequence, "{:?} then {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn every_timepoint_appears_once_at_its_scheduled_time(case in case()) {
+        let (timepoints, stop_times) = generate(&case);
+
+        let produced: Vec<(String, String)> = stop_times
+            .iter()
+            .filter(|st| st.timepoint == 1)
+            .map(|st| (st.stop_id.clone(), st.arrival_time.clone()))
+            .collect();
+        let expected: Vec<(String, String)> = timepoints
+            .iter()
+            .map(|(id, time)| (id.to_string(), time.clone().unwrap()))
+            .collect();
+
+        prop_assert_eq!(produced, expected);
+    }
+
+    #[test]
+    fn first_and_last_stops_are_pattern_endpoints(case in case()) {
+        let (_, stop_times) = generate(&case);
+
+        let first = case.sequence[case.timepoint_positions[case.first]].to_string();
+        let last = case.sequence[case.timepoint_positions[case.last]].to_string();
+        prop_assert_eq!(&stop_times.first().unwrap().stop_id, &first);
+        prop_assert_eq!(&stop_times.last().unwrap().stop_id, &last);
+    }
+}
+
+fn loop_sequence(ids: &[u32]) -> Vec<RawStop> {
+    ids.iter().map(|&id| raw_stop(id)).collect()
+}
+
+#[test]
+fn repeated_end_stop_is_ambiguous_without_an_occurrence() {
+    // Two loops: 1 2 3 1 | 1 2 3 1
+    let stops = loop_sequence(&[1, 2, 3, 1, 1, 2, 3, 1]);
+    let sequence: Vec<&RawStop> = stops.iter().collect();
+
+    let err = match_timepoints(&[Timepoint::stop(1), Timepoint::stop(3), Timepoint::stop(1)], &sequence).unwrap_err();
+    assert_eq!(err.code, "ambiguous_timepoint");
+    assert_eq!(err.stop_id.as_deref(), Some("1"));
+
+    let positions =
+        match_timepoints(&[Timepoint::nth(1, 1), Timepoint::stop(3), Timepoint::nth(1, 2)], &sequence).unwrap();
+    assert_eq!(positions, vec![0, 2, 3]);
+}
+
+#[test]
+fn missing_or_out_of_order_timepoints_are_rejected() {
+    let stops = loop_sequence(&[1, 2, 3, 4]);
+    let sequence: Vec<&RawStop> = stops.iter().collect();
+
+    let err = match_timepoints(&[Timepoint::stop(1), Timepoint::stop(9)], &sequence).unwrap_err();
+    assert_eq!(err.code, "timepoint_not_in_sequence");
+    assert_eq!(err.stop_id.as_deref(), Some("9"));
+
+    let err = match_timepoints(&[Timepoint::stop(3), Timepoint::nth(2, 1)], &sequence).unwrap_err();
+    assert_eq!(err.code, "timepoint_out_of_order");
+
+    let err = match_timepoints(&[Timepoint::stop(1), Timepoint::nth(4, 2)], &sequence).unwrap_err();
+    assert_eq!(err.code, "timepoint_occurrence_out_of_range");
+}
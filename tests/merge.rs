@@ -0,0 +1,98 @@
+//! Tests for the `merge` command and the transfers it writes to partner stops.
+
+use std::fs;
+
+mod common;
+
+use common::{copy_dir, fixtures_dir, run, run_generator_with};
+
+#[test]
+fn transfers_connect_stops_within_walking_distance_of_partner_stops() {
+    let workdir = tempfile::tempdir().unwrap();
+    let metrolink = fixtures_dir().join("partners/metrolink");
+    let config = format!(
+        "feed_id,path,id_prefix,max_walking_distance,walking_speed,by_route\nmetrolink,{},metrolink:,200,1.0,1\n",
+        metrolink.display()
+    );
+    run_generator_with(workdir.path(), &[], &[("partner_feeds.txt", &config)]);
+    // Our feed doesn't define the partner's stops and routes
+    assert!(!workdir.path().join("gtfs/transfers.txt").exists());
+
+    run(workdir.path(), &["merge", "--output", "merged"]);
+    let transfers = fs::read_to_string(workdir.path().join("merged/transfers.txt")).unwrap();
+    let rows: Vec<&str> = transfers.lines().collect();
+    assert_eq!(
+        rows,
+        [
+            "from_stop_id,to_stop_id,from_route_id,to_route_id,transfer_type,min_transfer_time",
+            "157583,metrolink:irvine_1,5956,metrolink:91,2,60",
+            "metrolink:irvine_1,157583,metrolink:91,5956,2,60",
+        ]
+    );
+}
+
+#[test]
+fn merge_renames_conflicting_ids_and_dedupes_stops() {
+    let workdir = tempfile::tempdir().unwrap();
+    let metrolink = fixtures_dir().join("partners/metrolink");
+    // No prefix, so only the partner's clashing service_id is renamed
+    let config = format!(
+        "feed_id,path,id_prefix,max_walking_distance\nmetrolink,{},,200\n",
+        metrolink.display()
+    );
+    run_generator_with(workdir.path(), &[], &[("partner_feeds.txt", &config)]);
+
+    run(workdir.path(), &["merge", "--output", "merged"]);
+    let merged = workdir.path().join("merged");
+    let trips = fs::read_to_string(merged.join("trips.txt")).unwrap();
+    assert!(trips.contains("\n91,metrolink:Weekday,600,"), "{}", trips);
+    let agencies = fs::read_to_string(merged.join("agency.txt")).unwrap();
+    assert_eq!(agencies.lines().count(), 3, "{}", agencies);
+    let routes = fs::read_to_string(merged.join("routes.txt")).unwrap();
+    assert!(routes.contains("\n91,metrolink,"), "{}", routes);
+
+    run(workdir.path(), &["merge", "--output", "deduped", "--dedupe-stops", "100"]);
+    let deduped = workdir.path().join("deduped");
+    let stops = fs::read_to_string(deduped.join("stops.txt")).unwrap();
+    assert!(!stops.contains("irvine_1"), "{}", stops);
+    assert!(stops.contains("\ntustin_1,"), "{}", stops);
+    let stop_times = fs::read_to_string(deduped.join("stop_times.txt")).unwrap();
+    assert!(stop_times.contains("600,06:31:00,06:32:00,157583,2"), "{}", stop_times);
+}
+
+#[test]
+fn merge_renames_block_and_fare_ids_and_drops_transfers_within_a_merged_stop() {
+    let workdir = tempfile::tempdir().unwrap();
+    let metrolink = workdir.path().join("metrolink");
+    copy_dir(&fixtures_dir().join("partners/metrolink"), &metrolink);
+    // A block and a network with the same ids as ours, and a second platform
+    fs::write(metrolink.join("trips.txt"), "route_id,service_id,trip_id,block_id\n91,Weekday,600,0520\n91,Weekday,601,0520\n")
+        .unwrap();
+    fs::write(metrolink.join("networks.txt"), "network_id,network_name\nirvine_connect,Metrolink\n").unwrap();
+    fs::write(metrolink.join("route_networks.txt"), "network_id,route_id\nirvine_connect,91\n").unwrap();
+    let stops = fs::read_to_string(metrolink.join("stops.txt")).unwrap();
+    fs::write(
+        metrolink.join("stops.txt"),
+        stops + "irvine_2,Irvine Station Platform 2,33.656900,-117.733500,0,irvine\n",
+    )
+    .unwrap();
+    fs::write(
+        metrolink.join("transfers.txt"),
+        "from_stop_id,to_stop_id,transfer_type,min_transfer_time\nirvine_1,irvine_2,2,120\nirvine_1,tustin_1,2,600\n",
+    )
+    .unwrap();
+    let config = format!("feed_id,path,id_prefix,max_walking_distance\nmetrolink,{},,200\n", metrolink.display());
+    run_generator_with(workdir.path(), &[], &[("partner_feeds.txt", &config)]);
+
+    run(workdir.path(), &["merge", "--output", "deduped", "--dedupe-stops", "100"]);
+    let deduped = workdir.path().join("deduped");
+    let trips = fs::read_to_string(deduped.join("trips.txt")).unwrap();
+    assert!(trips.contains("\n91,metrolink:Weekday,600,,metrolink:0520,"), "{}", trips);
+    let route_networks = fs::read_to_string(deduped.join("route_networks.txt")).unwrap();
+    assert!(route_networks.contains("\nmetrolink:irvine_connect,91\n"), "{}", route_networks);
+
+    // Both platforms became Dock 4, so only the transfer to Tustin is left
+    let transfers = fs::read_to_string(deduped.join("transfers.txt")).unwrap();
+    let rows: Vec<&str> = transfers.lines().collect();
+    assert_eq!(rows, ["from_stop_id,to_stop_id,transfer_type,min_transfer_time", "157583,tustin_1,2,600"]);
+}
@@ -0,0 +1,166 @@
+//! Tests for the `plan` command.
+
+use std::fs;
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{feed, fixtures_dir, partner_feed, run, run_generator};
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+use irvine_connect_schedule_gtfs::planner::{self, Itinerary};
+use irvine_connect_schedule_gtfs::time::parse_time;
+use irvine_connect_schedule_gtfs::transfers::PartnerConfig;
+
+fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 1, 5).unwrap()
+}
+
+fn metrolink(id_prefix: Option<&str>) -> PartnerConfig {
+    PartnerConfig {
+        feed_id: "metrolink".to_string(),
+        path: "metrolink".to_string(),
+        id_prefix: id_prefix.map(str::to_string),
+        max_walking_distance: 200.0,
+        walking_speed: None,
+        by_route: None,
+    }
+}
+
+/// Each leg as (trip_id or "walk", from, to, departure, arrival, stays_on_board).
+fn legs(itinerary: &Itinerary) -> Vec<(String, &str, &str, &str, &str, bool)> {
+    itinerary
+        .legs
+        .iter()
+        .map(|l| {
+            let how = l.trip_id.clone().unwrap_or("walk".to_string());
+            (how, l.from_stop_id.as_str(), l.to_stop_id.as_str(), l.departure_time.as_str(), l.arrival_time.as_str(), l.stays_on_board)
+        })
+        .collect()
+}
+
+#[test]
+fn plan_stays_aboard_through_dock_4_and_transfers_to_partner_trains() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let summary = run(workdir.path(), &["plan", "157625", "157584", "--date", "20260105", "--time", "07:30", "--itineraries", "2"]);
+    // Yale/Irvine is near the end of the loop and Ada @ Barranca just after Dock 4
+    assert!(summary.contains("Itinerary 1: 07:45:00 -> 08:41:00 (56 min, 0 transfers)"), "{}", summary);
+    assert!(summary.contains("  08:40:00 stay on as trip 5956_weekday_3_16 from Irvine Station (Dock 4) (157583) to Ada @ Barranca Pkwy (157584), arriving 08:41:00"), "{}", summary);
+    assert!(summary.contains("Itinerary 2: 08:05:00 -> 08:56:00"), "{}", summary);
+
+    let metrolink = fixtures_dir().join("partners/metrolink");
+    let config = format!("feed_id,path,id_prefix,max_walking_distance\nmetrolink,{},metrolink:,200\n", metrolink.display());
+    fs::write(workdir.path().join("input/partner_feeds.txt"), config).unwrap();
+    run(workdir.path(), &["plan", "157639", "metrolink:tustin_1", "--date", "20260105", "--time", "06:30", "--partners"]);
+    // Of the buses making train 601, the last one is kept
+    let legs = fs::read_to_string(workdir.path().join("plan.csv")).unwrap();
+    assert_eq!(
+        legs,
+        "itinerary,mode,feed_id,route_id,trip_id,from_stop_id,from_stop_name,departure_time,to_stop_id,to_stop_name,arrival_time,stays_on_board\n\
+         1,ride,irvine_connect,5956,5956_weekday_6_38,157639,Ada @ Alton Pkwy,07:23:00,157583,Irvine Station (Dock 4),07:25:00,false\n\
+         1,walk,,,,157583,Irvine Station (Dock 4),07:25:00,metrolink:irvine_1,Irvine Station Platform 1,07:25:50,false\n\
+         1,ride,metrolink,metrolink:91,metrolink:601,metrolink:irvine_1,Irvine Station Platform 1,07:41:00,metrolink:tustin_1,Tustin Station Platform 1,07:52:00,false\n"
+    );
+}
+
+#[test]
+fn riders_stay_aboard_when_the_block_goes_on_from_the_last_stop() {
+    let stops = [("a", 33.0, -117.0), ("b", 33.1, -117.0), ("c", 33.2, -117.0)];
+    let chained = feed(&stops, &[("t1", "S", "B1", &[("a", "08:00:00"), ("b", "08:10:00")]), ("t2", "S", "B1", &[("b", "08:15:00"), ("c", "08:30:00")])]);
+    let planner = planner::build(&chained, &[], date(), &mut Diagnostics::new());
+    let itineraries = planner.plan("a", "c", parse_time("07:55:00").unwrap(), 1).unwrap();
+    assert_eq!(itineraries[0].transfers, 0);
+    assert_eq!(
+        legs(&itineraries[0]),
+        vec![
+            ("t1".to_string(), "a", "b", "08:00:00", "08:10:00", false),
+            ("t2".to_string(), "b", "c", "08:15:00", "08:30:00", true),
+        ]
+    );
+
+    // Without a block, the same trips are a transfer
+    let unchained = feed(&stops, &[("t1", "S", "", &[("a", "08:00:00"), ("b", "08:10:00")]), ("t2", "S", "", &[("b", "08:15:00"), ("c", "08:30:00")])]);
+    let planner = planner::build(&unchained, &[], date(), &mut Diagnostics::new());
+    let itineraries = planner.plan("a", "c", parse_time("07:55:00").unwrap(), 1).unwrap();
+    assert_eq!(itineraries[0].transfers, 1);
+    assert!(!itineraries[0].legs[1].stays_on_board);
+}
+
+#[test]
+fn riders_walk_to_a_partner_stop_and_ride_from_there() {
+    // The partner's first stop is about 111 m north of ours, 93 s at 1.2 m/s
+    let ours = feed(&[("home", 33.0, -117.0)], &[]);
+    let theirs = partner_feed(&[("p1", 33.001, -117.0), ("p2", 33.1, -117.0)], &[("x", "S", "", &[("p1", "08:00:00"), ("p2", "08:20:00")])]);
+    let config = metrolink(Some("metrolink:"));
+    let planner = planner::build(&ours, &[(&config, theirs)], date(), &mut Diagnostics::new());
+
+    let itineraries = planner.plan("home", "metrolink:p2", parse_time("07:50:00").unwrap(), 3).unwrap();
+    assert_eq!(itineraries.len(), 1);
+    assert_eq!(
+        legs(&itineraries[0]),
+        vec![
+            ("walk".to_string(), "home", "metrolink:p1", "07:50:00", "07:51:33", false),
+            ("metrolink:x".to_string(), "metrolink:p1", "metrolink:p2", "08:00:00", "08:20:00", false),
+        ]
+    );
+}
+
+#[test]
+fn no_itineraries_when_the_destination_cannot_be_reached() {
+    let stops = [("a", 33.0, -117.0), ("b", 33.1, -117.0), ("c", 33.2, -117.0)];
+    let trips = feed(&stops, &[("t1", "S", "", &[("a", "08:00:00"), ("b", "08:10:00")])]);
+    let planner = planner::build(&trips, &[], date(), &mut Diagnostics::new());
+    assert!(planner.plan("a", "c", parse_time("07:00:00").unwrap(), 3).unwrap().is_empty());
+    // Nor after the last bus
+    assert!(planner.plan("a", "b", parse_time("08:01:00").unwrap(), 3).unwrap().is_empty());
+    assert!(planner.plan("a", "nowhere", 0, 3).is_err());
+}
+
+#[test]
+fn leaving_later_for_the_same_arrival_replaces_the_earlier_itinerary() {
+    let stops = [("a", 33.0, -117.0), ("b", 33.1, -117.0), ("c", 33.2, -117.0)];
+    let trips = feed(
+        &stops,
+        &[
+            ("t1", "S", "", &[("a", "08:00:00"), ("b", "08:10:00")]),
+            ("t2", "S", "", &[("a", "08:20:00"), ("b", "08:25:00")]),
+            ("t3", "S", "", &[("b", "08:30:00"), ("c", "08:40:00")]),
+        ],
+    );
+    let planner = planner::build(&trips, &[], date(), &mut Diagnostics::new());
+    // t1 and t2 both make t3
+    let itineraries = planner.plan("a", "c", parse_time("07:55:00").unwrap(), 3).unwrap();
+    assert_eq!(itineraries.len(), 1);
+    assert_eq!(
+        legs(&itineraries[0]),
+        vec![
+            ("t2".to_string(), "a", "b", "08:20:00", "08:25:00", false),
+            ("t3".to_string(), "b", "c", "08:30:00", "08:40:00", false),
+        ]
+    );
+}
+
+#[test]
+fn partner_stop_ids_clashing_with_ours_are_renamed() {
+    let ours = feed(&[("a", 33.0, -117.0)], &[]);
+    let theirs = partner_feed(&[("a", 33.0, -117.0), ("z", 33.1, -117.0)], &[("x", "S", "", &[("a", "08:00:00"), ("z", "08:20:00")])]);
+    let config = metrolink(None);
+    let mut diagnostics = Diagnostics::new();
+    let planner = planner::build(&ours, &[(&config, theirs)], date(), &mut diagnostics);
+
+    let messages: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code, d.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        vec![("planner_stop_id_conflict", "1 stop id(s) in metrolink are also used by an earlier feed; renamed to metrolink:<id>")]
+    );
+    // Our stop and theirs are kept apart, a walk of no time between them
+    let itineraries = planner.plan("a", "z", parse_time("07:55:00").unwrap(), 1).unwrap();
+    assert_eq!(
+        legs(&itineraries[0]),
+        vec![
+            ("walk".to_string(), "a", "metrolink:a", "07:55:00", "07:55:00", false),
+            ("x".to_string(), "metrolink:a", "z", "08:00:00", "08:20:00", false),
+        ]
+    );
+}
@@ -0,0 +1,163 @@
+//! Tests for applying scenario changes (`scenario::apply`) and the `scenario`
+//! command.
+
+use std::fs;
+
+mod common;
+
+use irvine_connect_schedule_gtfs::data::{Pattern, RouteSchedule, Timepoint, TripInput};
+use irvine_connect_schedule_gtfs::diagnostics::Diagnostics;
+use irvine_connect_schedule_gtfs::scenario::{self, Change, ChangeKind};
+
+use common::{run, run_failing, run_generator};
+
+const WHOLE_ROUTE: Pattern = Pattern { first: 0, last: 2, headsign: None };
+
+/// A route through timepoints 1, 2 and 3, ten minutes apart, with trips from the
+/// first to the last starting at `starts` (service_id, HH:MM).
+fn route(starts: &[(&str, &str)]) -> RouteSchedule {
+    RouteSchedule {
+        route_id: "R",
+        shape_id: "R",
+        timepoints: vec![Timepoint::stop(1), Timepoint::stop(2), Timepoint::stop(3)],
+        offsets: vec![0, 10, 20],
+        loop_length: 3,
+        headsigns: &[],
+        trips: starts
+            .iter()
+            .enumerate()
+            .map(|(i, (service_id, start))| TripInput {
+                trip_id: format!("t{}", i + 1),
+                raw_index: i,
+                route_id: "R",
+                service_id: service_id.to_string(),
+                block_id: "1".to_string(),
+                wheelchair_accessible: 1,
+                bikes_allowed: 1,
+                pattern: WHOLE_ROUTE,
+                stops: vec![(1, Some(start.to_string()))],
+            })
+            .collect(),
+    }
+}
+
+fn change(change: ChangeKind, service_id: Option<&str>) -> Change {
+    Change {
+        change,
+        route_id: "R".to_string(),
+        service_id: service_id.map(str::to_string),
+        timepoint: None,
+        minutes: None,
+        first: None,
+        last: None,
+        start_time: None,
+        end_time: None,
+    }
+}
+
+/// Each trip's (trip_id, service_id, times at its timepoints).
+fn timetable(route: &RouteSchedule) -> Vec<(&str, &str, Vec<&str>)> {
+    route
+        .trips
+        .iter()
+        .map(|t| (t.trip_id.as_str(), t.service_id.as_str(), t.stops.iter().map(|(_, time)| time.as_deref().unwrap()).collect()))
+        .collect()
+}
+
+#[test]
+fn scenario_reports_fleet_and_service_hour_changes() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+    let changes = "change,route_id,service_id,timepoint,minutes,first,last,start_time,end_time\n\
+                   offset,5956,,6,5,,,,\n\
+                   headway,5956,Weekday,,20,0,7,06:00,20:00\n";
+    fs::write(workdir.path().join("changes.txt"), changes).unwrap();
+    let feed_before = fs::read_to_string(workdir.path().join("gtfs/stop_times.txt")).unwrap();
+
+    let summary = run(workdir.path(), &["scenario", "changes.txt", "--min-layover", "5", "--year", "2026"]);
+    // A 115-minute loop plus 5 minutes' layover every 20 minutes needs six buses
+    assert!(summary.contains("Weekday: 7 -> 6 vehicles, 28 -> 0 layover violations, 75.17 -> 80.50 revenue hours a day"), "{}", summary);
+    assert!(summary.contains("Weekend: 7 -> 7 vehicles"), "{}", summary);
+
+    let rows = fs::read_to_string(workdir.path().join("scenario.csv")).unwrap();
+    assert!(rows.contains("\nWeekday,44,42,7,6,28,0,75.17,80.5,5.33,1370.67\n"), "{}", rows);
+    // Nothing is written to the feed
+    assert_eq!(fs::read_to_string(workdir.path().join("gtfs/stop_times.txt")).unwrap(), feed_before);
+}
+
+#[test]
+fn offset_lengthens_the_leg_and_every_later_one() {
+    let mut schedules = [route(&[("Weekday", "06:00"), ("Weekend", "07:00")])];
+    let offset = Change { timepoint: Some(1), minutes: Some(5), ..change(ChangeKind::Offset, None) };
+    let mut diagnostics = Diagnostics::new();
+
+    scenario::apply(&mut schedules, &[(2, offset)], "changes.txt", &mut diagnostics);
+    assert!(!diagnostics.has_errors());
+    assert_eq!(schedules[0].offsets, [0, 15, 25]);
+    assert_eq!(
+        timetable(&schedules[0]),
+        [
+            ("t1", "Weekday", vec!["06:00:00", "06:15:00", "06:25:00"]),
+            ("t2", "Weekend", vec!["07:00:00", "07:15:00", "07:25:00"]),
+        ]
+    );
+}
+
+#[test]
+fn offset_that_would_put_a_timepoint_before_the_one_before_is_rejected() {
+    let mut schedules = [route(&[("Weekday", "06:00")])];
+    // Ten minutes back is level with timepoint 0, eleven is before it
+    let level = Change { timepoint: Some(1), minutes: Some(-10), ..change(ChangeKind::Offset, None) };
+    let before = Change { timepoint: Some(2), minutes: Some(-11), ..change(ChangeKind::Offset, None) };
+    let mut diagnostics = Diagnostics::new();
+
+    scenario::apply(&mut schedules, &[(2, level), (3, before)], "changes.txt", &mut diagnostics);
+    let messages: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code, d.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        vec![(
+            "invalid_scenario",
+            "Timepoint 2 would be -1 min from the first, before timepoint 1 at 0 min; offsets must not decrease"
+        )]
+    );
+    assert_eq!(schedules[0].offsets, [0, 0, 10]);
+}
+
+#[test]
+fn negative_min_layover_is_rejected() {
+    let workdir = tempfile::tempdir().unwrap();
+    let stderr = run_failing(workdir.path(), &["scenario", "changes.txt", "--min-layover", "-5"]);
+    assert!(stderr.starts_with("Invalid number of minutes: -5\nUsage: "), "{}", stderr);
+}
+
+#[test]
+fn headway_replaces_only_the_trips_starting_in_its_window() {
+    let mut schedules = [route(&[("Weekday", "06:00"), ("Weekday", "06:30"), ("Weekday", "07:00"), ("Weekend", "06:00")])];
+    let headway = Change {
+        minutes: Some(20),
+        first: Some(0),
+        last: Some(2),
+        start_time: Some("06:00".to_string()),
+        end_time: Some("07:00".to_string()),
+        ..change(ChangeKind::Headway, Some("Weekday"))
+    };
+    let mut diagnostics = Diagnostics::new();
+
+    scenario::apply(&mut schedules, &[(2, headway)], "changes.txt", &mut diagnostics);
+    assert!(!diagnostics.has_errors());
+    let trips = timetable(&schedules[0]);
+    let starts: Vec<(&str, &str, &str)> = trips.iter().map(|(trip_id, service_id, times)| (*trip_id, *service_id, times[0])).collect();
+    assert_eq!(
+        starts,
+        [
+            ("t3", "Weekday", "07:00:00"),
+            ("t4", "Weekend", "06:00:00"),
+            ("R_weekday_scenario2_1", "Weekday", "06:00:00"),
+            ("R_weekday_scenario2_2", "Weekday", "06:20:00"),
+            ("R_weekday_scenario2_3", "Weekday", "06:40:00"),
+        ]
+    );
+    // The new trips run the whole route, without a block
+    assert_eq!(trips[3].2, ["06:20:00", "06:30:00", "06:40:00"]);
+    assert!(schedules[0].trips[2..].iter().all(|t| t.block_id.is_empty()));
+}
@@ -0,0 +1,100 @@
+//! Tests for special service days, event trips and holidays in the generated
+//! calendar.
+
+use std::fs;
+
+mod common;
+
+use common::run_generator_with;
+
+#[test]
+fn special_service_days_add_service() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator_with(
+        workdir.path(),
+        &["--report", "report.json"],
+        &[(
+            "special_service.txt",
+            "date,service,description\n20260918,Weekend,Great Park event\n20260919,Weekend,Already a Saturday\n",
+        )],
+    );
+
+    let calendar_dates = fs::read_to_string(workdir.path().join("gtfs/calendar_dates.txt")).unwrap();
+    assert!(calendar_dates.lines().any(|l| l == "Weekend,20260918,1"), "{}", calendar_dates);
+    assert!(!calendar_dates.contains("20260919"), "{}", calendar_dates);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("report.json")).unwrap()).unwrap();
+    let codes: Vec<&str> = report["diagnostics"].as_array().unwrap().iter().map(|d| d["code"].as_str().unwrap()).collect();
+    assert!(codes.contains(&"special_service_already_scheduled"), "{:?}", codes);
+}
+
+#[test]
+fn event_trips_run_on_their_special_service_days() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator_with(
+        workdir.path(),
+        &["--report", "report.json"],
+        &[
+            (
+                "event_trips.txt",
+                "service,route_id,bus_id,block_id,start_time,first_timepoint,last_timepoint\nGreatParkEvent,5956,7,EV1,21:30,0,4\nGreatParkEvent,5956,7,EV1,22:40,4,7\n",
+            ),
+            ("special_service.txt", "date,service,description\n20260918,GreatParkEvent,Great Park concert\n"),
+        ],
+    );
+
+    let calendar_dates = fs::read_to_string(workdir.path().join("gtfs/calendar_dates.txt")).unwrap();
+    assert!(calendar_dates.lines().any(|l| l == "GreatParkEvent,20260918,1"), "{}", calendar_dates);
+
+    let trips = fs::read_to_string(workdir.path().join("gtfs/trips.txt")).unwrap();
+    let event_trips: Vec<&str> = trips.lines().filter(|l| l.contains(",GreatParkEvent,")).collect();
+    assert_eq!(event_trips.len(), 2, "{}", trips);
+    assert!(event_trips[0].contains(",5956_greatparkevent_7_1,"), "{:?}", event_trips);
+
+    // Dock 4 at 21:30, Yale/Irvine 65 minutes later
+    let stop_times = fs::read_to_string(workdir.path().join("gtfs/stop_times.txt")).unwrap();
+    assert!(stop_times.contains("\n5956_greatparkevent_7_1,21:30:00,21:30:00,157583,"), "{}", stop_times);
+    assert!(stop_times.contains("\n5956_greatparkevent_7_2,22:40:00,22:40:00,157625,"), "{}", stop_times);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(workdir.path().join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["errors"], 0, "{}", report["diagnostics"]);
+}
+
+#[test]
+fn holidays_remove_regular_service_and_add_the_substitute() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator_with(
+        workdir.path(),
+        &[],
+        &[(
+            "holidays.txt",
+            "date,name,substitute_service\n20261126,Thanksgiving,\n20261127,Day after Thanksgiving,Weekend\n20261129,No service Sunday,\n",
+        )],
+    );
+
+    let calendar_dates = fs::read_to_string(workdir.path().join("gtfs/calendar_dates.txt")).unwrap();
+    let rows: Vec<&str> = calendar_dates.lines().skip(1).collect();
+    assert_eq!(
+        rows,
+        ["Weekday,20261126,2", "Weekday,20261127,2", "Weekend,20261127,1", "Weekend,20261129,2"]
+    );
+}
+
+#[test]
+fn holidays_remove_only_the_listed_services() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator_with(
+        workdir.path(),
+        &[],
+        &[(
+            "holidays.txt",
+            "date,name,removed_services,substitute_service\n20261128,Weekend parade,Weekday,\n20261130,Weekend on a Monday,Weekday Weekend,Weekend\n",
+        )],
+    );
+
+    let calendar_dates = fs::read_to_string(workdir.path().join("gtfs/calendar_dates.txt")).unwrap();
+    let rows: Vec<&str> = calendar_dates.lines().skip(1).collect();
+    assert_eq!(rows, ["Weekday,20261128,2", "Weekday,20261130,2"]);
+}
@@ -0,0 +1,33 @@
+//! Tests for the `shifts` command.
+
+use std::fs;
+
+mod common;
+
+use common::{fixtures_dir, run, run_failing, run_generator_with};
+
+#[test]
+fn shifts_move_trips_towards_trains_within_bounds() {
+    let workdir = tempfile::tempdir().unwrap();
+    let metrolink = fixtures_dir().join("partners/metrolink");
+    let config = format!("feed_id,path,id_prefix,max_walking_distance\nmetrolink,{},,200\n", metrolink.display());
+    run_generator_with(workdir.path(), &[], &[("partner_feeds.txt", &config)]);
+
+    let summary = run(workdir.path(), &["shifts", "--date", "20261019", "--max-shift", "10"]);
+    assert!(summary.contains("Weekday (20261019): 5 of 44 trips shifted, total wait 2421 -> 2358 min"), "{}", summary);
+
+    let diff = fs::read_to_string(workdir.path().join("schedule_shifts.diff")).unwrap();
+    // Train 600 arrives 06:31, one minute's walk from the 06:40 departure
+    assert!(diff.contains("-route 5956 Weekday RawTrip #14: start_time: \"06:40\"\n"), "{}", diff);
+    assert!(diff.contains("+route 5956 Weekday RawTrip #14: start_time: \"06:32\" (-8 min)\n"), "{}", diff);
+}
+
+#[test]
+fn negative_limits_are_rejected() {
+    let workdir = tempfile::tempdir().unwrap();
+    for option in ["--max-shift", "--min-layover", "--window"] {
+        let stderr = run_failing(workdir.path(), &["shifts", option, "-1"]);
+        assert!(stderr.starts_with("Invalid number of minutes: -1\nUsage: "), "{}: {}", option, stderr);
+    }
+    assert!(!workdir.path().join("schedule_shifts.diff").exists());
+}
@@ -0,0 +1,34 @@
+//! Tests for the `stats` command.
+
+use std::fs;
+
+mod common;
+
+use common::{run, run_generator};
+
+#[test]
+fn stats_total_revenue_hours_and_miles_by_trip_block_and_service() {
+    let workdir = tempfile::tempdir().unwrap();
+    run_generator(workdir.path(), &[]);
+
+    let summary = run(workdir.path(), &["stats", "--year", "2026"]);
+    // Blocks 0520 and 0535 each run two buses at once
+    assert!(summary.contains("Weekday: 44 trips, 6 peak vehicles"), "{}", summary);
+
+    let stats = workdir.path().join("stats");
+    let services = fs::read_to_string(stats.join("services.csv")).unwrap();
+    // 261 weekdays in 2026, less the four holidays falling on one
+    assert!(services.contains("\nWeekday,44,4,6,75.17,6.0,1094.71,257,19317.83,1542.0,281339.49\n"), "{}", services);
+
+    let blocks = fs::read_to_string(stats.join("blocks.csv")).unwrap();
+    assert!(blocks.contains("\nWeekday,0520,15,2,06:00:00,20:00:00,25.67,2.08,373.8\n"), "{}", blocks);
+
+    // A trip from Yale/Irvine back to Dock 4 and one the whole loop round
+    let trips = fs::read_to_string(stats.join("trips.csv")).unwrap();
+    assert!(trips.contains("\n5956_weekday_4_22,5956,Weekday,0520,06:00:00,06:45:00,0.75,10.7\n"), "{}", trips);
+    assert!(trips.contains("\n5956_weekday_4_23,5956,Weekday,0520,07:00:00,08:50:00,1.83,26.7\n"), "{}", trips);
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(stats.join("stats.json")).unwrap()).unwrap();
+    assert_eq!(json["services"][1]["service_id"], "Weekend");
+    assert_eq!(json["blocks"].as_array().unwrap().len(), 8);
+}